@@ -0,0 +1,490 @@
+// Backtester: replays historical candles through the live signal pipeline
+//
+// Base candles (from CSV files or the Delta history API) are resampled into
+// every timeframe the pipeline needs, and the pipeline is evaluated each time a
+// base candle closes, exactly as the live loop does. Signals fill at the next
+// base candle's open with slippage; fees are charged per side and funding is
+// charged at every funding interval a position is held across.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::delta::{self, Candle};
+use crate::history::{self, HistoryConfig};
+use crate::pipeline::{Pipeline, SymbolCandles};
+use crate::ranking;
+use crate::sizing::{self, SizingConfig, SizingInput};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct BacktestConfig {
+    pub symbols: Vec<String>,
+    // "file" reads <data_dir>/<SYMBOL>.csv, "api" downloads from Delta
+    pub source: String,
+    pub data_dir: String,
+    // Timeframe of the input candles; higher timeframes are built from it
+    pub base_timeframe: String,
+    // Unix seconds; used by the API source and to trim file data (0 = unbounded)
+    pub start: u64,
+    pub end: u64,
+    pub initial_equity: f64,
+    // Per side, as % of notional
+    pub fee_pct: f64,
+    pub slippage_pct: f64,
+    // Charged to longs (paid to shorts) every funding interval, as % of notional
+    pub funding_rate_pct: f64,
+    pub funding_interval_hours: u64,
+    // Entry notional; the stop is stop_loss_pct from entry when that is set
+    pub sizing: SizingConfig,
+    pub allow_short: bool,
+    // Exits besides the opposite signal (0 = off)
+    pub stop_loss_pct: f64,
+    pub take_profit_pct: f64,
+    // Trade list and equity curve
+    pub output: String,
+    // Statistics as JSON and the HTML report
+    pub metrics_output: String,
+    pub report_output: String,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            symbols: vec!["BTCUSD".to_string()],
+            source: "file".to_string(),
+            data_dir: "data".to_string(),
+            base_timeframe: "5m".to_string(),
+            start: 0,
+            end: 0,
+            initial_equity: 10_000.0,
+            fee_pct: 0.05,
+            slippage_pct: 0.02,
+            funding_rate_pct: 0.01,
+            funding_interval_hours: 8,
+            sizing: SizingConfig { notional: 1_000.0, ..SizingConfig::default() },
+            allow_short: true,
+            stop_loss_pct: 0.0,
+            take_profit_pct: 0.0,
+            output: "backtest.json".to_string(),
+            metrics_output: "backtest_metrics.json".to_string(),
+            report_output: "backtest_report.html".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Trade {
+    pub symbol: String,
+    pub timeframe: String,
+    // "long" or "short"
+    pub side: &'static str,
+    pub entry_time: u64,
+    pub entry_price: f64,
+    pub exit_time: u64,
+    pub exit_price: f64,
+    pub quantity: f64,
+    // Net of fees and funding
+    pub pnl: f64,
+    pub fees: f64,
+    pub funding: f64,
+    // Net PnL over entry notional, in %
+    pub return_pct: f64,
+    pub entry_score: f64,
+    // "signal", "stop_loss", "take_profit" or "end"
+    pub exit_reason: &'static str,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct EquityPoint {
+    pub timestamp: u64,
+    pub equity: f64,
+    pub open_positions: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct BacktestResult {
+    pub initial_equity: f64,
+    pub final_equity: f64,
+    pub trades: Vec<Trade>,
+    pub equity: Vec<EquityPoint>,
+}
+
+struct Position {
+    side: &'static str,
+    timeframe: String,
+    entry_time: u64,
+    entry_price: f64,
+    quantity: f64,
+    fees: f64,
+    funding: f64,
+    score: f64,
+}
+
+impl Position {
+    fn sign(&self) -> f64 {
+        if self.side == "long" { 1.0 } else { -1.0 }
+    }
+
+    fn unrealized(&self, price: f64) -> f64 {
+        (price - self.entry_price) * self.quantity * self.sign()
+    }
+}
+
+// Signal waiting to fill at the next base candle's open
+struct PendingOrder {
+    direction: &'static str,
+    timeframe: String,
+    score: f64,
+    // The signal's sizing override and the ATR of its timeframe at the signal
+    sizing: Option<SizingConfig>,
+    atr: Option<f64>,
+}
+
+// Builds closed candles per timeframe incrementally from base candles
+struct Resampler {
+    timeframes: Vec<(String, u64)>,
+    base_sec: u64,
+    // Closed candles kept per timeframe
+    depth: HashMap<String, usize>,
+    candles: SymbolCandles,
+    building: HashMap<String, Candle>,
+}
+
+impl Resampler {
+    fn new(timeframes: &[String], base_sec: u64, depth: HashMap<String, usize>) -> Self {
+        let timeframes = timeframes.iter()
+            .filter_map(|tf| Some((tf.clone(), delta::timeframe_seconds(tf)?)))
+            .filter(|(_, secs)| *secs >= base_sec)
+            .collect();
+        Self { timeframes, base_sec, depth, candles: SymbolCandles::default(), building: HashMap::new() }
+    }
+
+    fn push(&mut self, base: &Candle) {
+        for (tf, secs) in &self.timeframes {
+            let bucket = base.timestamp - base.timestamp % secs;
+            let candle = self.building.entry(tf.clone()).or_insert_with(|| Candle { timestamp: bucket, volume: 0.0, ..base.clone() });
+            if candle.timestamp != bucket {
+                // The previous bucket had a gap at its end; close it as is
+                let done = std::mem::replace(candle, Candle { timestamp: bucket, volume: 0.0, ..base.clone() });
+                Self::close(&mut self.candles, tf, done, self.depth[tf]);
+            }
+            let candle = self.building.get_mut(tf).unwrap();
+            candle.high = candle.high.max(base.high);
+            candle.low = candle.low.min(base.low);
+            candle.close = base.close;
+            candle.volume += base.volume;
+            if base.timestamp + self.base_sec >= bucket + secs {
+                let done = self.building.remove(tf).unwrap();
+                Self::close(&mut self.candles, tf, done, self.depth[tf]);
+            }
+        }
+    }
+
+    fn close(candles: &mut SymbolCandles, tf: &str, candle: Candle, history: usize) {
+        let series = candles.closed.entry(tf.to_string()).or_default();
+        series.push(candle);
+        if series.len() > history {
+            series.remove(0);
+        }
+    }
+}
+
+pub struct Backtester {
+    cfg: BacktestConfig,
+    history: HistoryConfig,
+    pipeline: Pipeline,
+    equity: f64,
+    positions: HashMap<String, Position>,
+    pending: HashMap<String, PendingOrder>,
+    last_price: HashMap<String, f64>,
+    trades: Vec<Trade>,
+    curve: Vec<EquityPoint>,
+    // Candles before this only warm up the indicators
+    trade_from: u64,
+}
+
+impl Backtester {
+    // Candles are kept per timeframe as deep as the live history keeps them
+    pub fn new(cfg: BacktestConfig, history: HistoryConfig, mut pipeline: Pipeline) -> Self {
+        // Only closed candles are replayed, so there is nothing to preview
+        pipeline.intrabar_preview = false;
+        let equity = cfg.initial_equity;
+        Self {
+            cfg,
+            history,
+            pipeline,
+            equity,
+            positions: HashMap::new(),
+            pending: HashMap::new(),
+            last_price: HashMap::new(),
+            trades: Vec::new(),
+            curve: Vec::new(),
+            trade_from: 0,
+        }
+    }
+
+    // No orders are placed and no equity is recorded before `ts`
+    pub fn trading_from(mut self, ts: u64) -> Self {
+        self.trade_from = ts;
+        self
+    }
+
+    pub fn run(mut self, data: &HashMap<String, Vec<Candle>>) -> Result<BacktestResult, String> {
+        let base_sec = delta::timeframe_seconds(&self.cfg.base_timeframe)
+            .ok_or_else(|| format!("invalid base timeframe '{}'", self.cfg.base_timeframe))?;
+        let timeframes = self.pipeline.timeframes();
+        let ranking_cfg = self.pipeline.scoring.config.ranking.clone();
+        let ranking_sec = delta::timeframe_seconds(&ranking_cfg.timeframe).unwrap_or(3600);
+        let depth: HashMap<String, usize> = timeframes.iter()
+            .map(|tf| (tf.clone(), history::depth(&self.history, &self.pipeline.scoring.config, tf)))
+            .collect();
+        let mut resamplers: HashMap<String, Resampler> = data.keys()
+            .map(|s| (s.clone(), Resampler::new(&timeframes, base_sec, depth.clone())))
+            .collect();
+        let mut timeline: BTreeMap<u64, Vec<(&String, &Candle)>> = BTreeMap::new();
+        for (symbol, candles) in data {
+            for c in candles {
+                timeline.entry(c.timestamp).or_default().push((symbol, c));
+            }
+        }
+
+        for (&ts, bar) in &timeline {
+            for &(symbol, candle) in bar {
+                self.fill_pending(symbol, candle);
+                self.check_exits(symbol, candle);
+                self.charge_funding(symbol, candle, base_sec);
+                self.last_price.insert(symbol.clone(), candle.close);
+                resamplers.get_mut(symbol).unwrap().push(candle);
+            }
+            let now = ts + base_sec;
+            if data.len() > 1 && now % ranking_sec == 0 {
+                let ranking_candles: HashMap<String, Vec<Candle>> = resamplers.iter()
+                    .filter_map(|(s, r)| Some((s.clone(), r.candles.closed.get(&ranking_cfg.timeframe)?.clone())))
+                    .collect();
+                self.pipeline.set_rankings(&ranking::rank(&ranking_candles, &ranking_cfg));
+            }
+            for &(symbol, _) in bar {
+                let candles = &resamplers[symbol].candles;
+                for sig in self.pipeline.evaluate(symbol, candles, now) {
+                    if sig.provisional || now < self.trade_from {
+                        continue;
+                    }
+                    let atr_period = sig.sizing.as_ref().unwrap_or(&self.cfg.sizing).atr_period;
+                    let atr = candles.closed.get(&sig.timeframe).and_then(|c| sizing::latest_atr(c, atr_period));
+                    // The first signal of a close wins, like the live alerts
+                    self.pending.entry(symbol.clone()).or_insert(PendingOrder {
+                        direction: sig.direction,
+                        timeframe: sig.timeframe,
+                        score: sig.score,
+                        sizing: sig.sizing,
+                        atr,
+                    });
+                }
+            }
+            if now >= self.trade_from {
+                self.mark(now);
+            }
+        }
+
+        let end = timeline.keys().next_back().map_or(0, |ts| ts + base_sec);
+        let symbols: Vec<String> = self.positions.keys().cloned().collect();
+        for symbol in symbols {
+            let price = self.last_price[&symbol];
+            self.close_position(&symbol, price, end, "end");
+        }
+        self.mark(end);
+        Ok(BacktestResult {
+            initial_equity: self.cfg.initial_equity,
+            final_equity: self.equity,
+            trades: self.trades,
+            equity: self.curve,
+        })
+    }
+
+    fn fill_pending(&mut self, symbol: &str, candle: &Candle) {
+        let order = match self.pending.remove(symbol) {
+            Some(o) => o,
+            None => return,
+        };
+        let side = if order.direction == "buy" { "long" } else { "short" };
+        if let Some(pos) = self.positions.get(symbol) {
+            if pos.side == side {
+                return;
+            }
+            let exit = self.slipped(candle.open, pos.side, false);
+            self.close_position(symbol, exit, candle.timestamp, "signal");
+        }
+        if side == "short" && !self.cfg.allow_short {
+            return;
+        }
+        let price = self.slipped(candle.open, side, true);
+        let sign = if side == "long" { 1.0 } else { -1.0 };
+        let input = SizingInput {
+            equity: self.equity,
+            price,
+            stop_price: (self.cfg.stop_loss_pct > 0.0).then(|| price * (1.0 - sign * self.cfg.stop_loss_pct / 100.0)),
+            atr: order.atr,
+            ..SizingInput::default()
+        };
+        // An entry the model cannot size (e.g. no stop or ATR yet) is skipped
+        let notional = sizing::notional(order.sizing.as_ref().unwrap_or(&self.cfg.sizing), &input).unwrap_or(0.0);
+        if notional <= 0.0 || price <= 0.0 {
+            return;
+        }
+        let fee = notional * self.cfg.fee_pct / 100.0;
+        self.equity -= fee;
+        self.positions.insert(symbol.to_string(), Position {
+            side,
+            timeframe: order.timeframe,
+            entry_time: candle.timestamp,
+            entry_price: price,
+            quantity: notional / price,
+            fees: fee,
+            funding: 0.0,
+            score: order.score,
+        });
+    }
+
+    // Fill price after slippage: entries and exits both move against the position
+    fn slipped(&self, price: f64, side: &str, entry: bool) -> f64 {
+        let adverse = (side == "long") == entry;
+        let slip = price * self.cfg.slippage_pct / 100.0;
+        if adverse { price + slip } else { price - slip }
+    }
+
+    // Stops are checked before targets when a candle touches both
+    fn check_exits(&mut self, symbol: &str, candle: &Candle) {
+        let (stop, target, side) = match self.positions.get(symbol) {
+            Some(pos) => {
+                let s = pos.sign();
+                let stop = (self.cfg.stop_loss_pct > 0.0).then(|| pos.entry_price * (1.0 - s * self.cfg.stop_loss_pct / 100.0));
+                let target = (self.cfg.take_profit_pct > 0.0).then(|| pos.entry_price * (1.0 + s * self.cfg.take_profit_pct / 100.0));
+                (stop, target, pos.side)
+            }
+            None => return,
+        };
+        let long = side == "long";
+        if let Some(stop) = stop {
+            if (long && candle.low <= stop) || (!long && candle.high >= stop) {
+                // Gaps through the stop fill at the open
+                let level = if long { stop.min(candle.open) } else { stop.max(candle.open) };
+                let exit = self.slipped(level, side, false);
+                self.close_position(symbol, exit, candle.timestamp, "stop_loss");
+                return;
+            }
+        }
+        if let Some(target) = target {
+            if (long && candle.high >= target) || (!long && candle.low <= target) {
+                let exit = self.slipped(target, side, false);
+                self.close_position(symbol, exit, candle.timestamp, "take_profit");
+            }
+        }
+    }
+
+    fn charge_funding(&mut self, symbol: &str, candle: &Candle, base_sec: u64) {
+        let interval = self.cfg.funding_interval_hours * 3600;
+        if interval == 0 {
+            return;
+        }
+        // Funding is settled at each interval boundary up to this candle's close
+        let crossings = (candle.timestamp + base_sec) / interval - candle.timestamp / interval;
+        if let Some(pos) = self.positions.get_mut(symbol) {
+            if crossings > 0 {
+                let payment = pos.quantity * candle.close * self.cfg.funding_rate_pct / 100.0 * pos.sign() * crossings as f64;
+                pos.funding += payment;
+                self.equity -= payment;
+            }
+        }
+    }
+
+    fn close_position(&mut self, symbol: &str, price: f64, time: u64, reason: &'static str) {
+        let pos = match self.positions.remove(symbol) {
+            Some(p) => p,
+            None => return,
+        };
+        let fee = pos.quantity * price * self.cfg.fee_pct / 100.0;
+        let gross = pos.unrealized(price);
+        self.equity += gross - fee;
+        let fees = pos.fees + fee;
+        let pnl = gross - fees - pos.funding;
+        let notional = pos.quantity * pos.entry_price;
+        self.trades.push(Trade {
+            symbol: symbol.to_string(),
+            timeframe: pos.timeframe,
+            side: pos.side,
+            entry_time: pos.entry_time,
+            entry_price: pos.entry_price,
+            exit_time: time,
+            exit_price: price,
+            quantity: pos.quantity,
+            pnl,
+            fees,
+            funding: pos.funding,
+            return_pct: if notional > 0.0 { pnl / notional * 100.0 } else { 0.0 },
+            entry_score: pos.score,
+            exit_reason: reason,
+        });
+    }
+
+    fn mark(&mut self, timestamp: u64) {
+        let unrealized: f64 = self.positions.iter()
+            .map(|(s, p)| p.unrealized(self.last_price.get(s).copied().unwrap_or(p.entry_price)))
+            .sum();
+        self.curve.push(EquityPoint { timestamp, equity: self.equity + unrealized, open_positions: self.positions.len() });
+    }
+}
+
+// Reads "timestamp,open,high,low,close,volume" rows; a header line is skipped
+pub fn load_candles_csv(path: &str) -> Result<Vec<Candle>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut candles = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if line.trim().is_empty() || (i == 0 && fields[0].parse::<f64>().is_err()) {
+            continue;
+        }
+        if fields.len() < 6 {
+            return Err(format!("{}:{}: expected 6 columns", path, i + 1));
+        }
+        let num = |j: usize| fields[j].parse::<f64>().map_err(|e| format!("{}:{}: {}", path, i + 1, e));
+        candles.push(Candle {
+            timestamp: num(0)? as u64,
+            open: num(1)?,
+            high: num(2)?,
+            low: num(3)?,
+            close: num(4)?,
+            volume: num(5)?,
+        });
+    }
+    candles.sort_by_key(|c| c.timestamp);
+    Ok(candles)
+}
+
+pub fn save_candles_csv(path: &str, candles: &[Candle]) -> Result<(), String> {
+    let mut out = String::from("timestamp,open,high,low,close,volume\n");
+    for c in candles {
+        out.push_str(&format!("{},{},{},{},{},{}\n", c.timestamp, c.open, c.high, c.low, c.close, c.volume));
+    }
+    std::fs::write(path, out).map_err(|e| format!("{}: {}", path, e))
+}
+
+// Loads base candles for every configured symbol from files or the history API
+pub async fn load_data(cfg: &BacktestConfig, client: &delta::DeltaClient) -> Result<HashMap<String, Vec<Candle>>, String> {
+    let mut data = HashMap::new();
+    for symbol in &cfg.symbols {
+        let path = format!("{}/{}.csv", cfg.data_dir, symbol);
+        let mut candles = if cfg.source == "api" {
+            let end = if cfg.end > 0 { cfg.end } else { chrono::Utc::now().timestamp() as u64 };
+            let candles = client.fetch_history_candles(symbol, &cfg.base_timeframe, cfg.start, end).await
+                .map_err(|e| format!("{}: {}", symbol, e))?;
+            // Cache the download so later runs can use the file source
+            if std::fs::create_dir_all(&cfg.data_dir).is_ok() {
+                save_candles_csv(&path, &candles)?;
+            }
+            candles
+        } else {
+            load_candles_csv(&path)?
+        };
+        candles.retain(|c| c.timestamp >= cfg.start && (cfg.end == 0 || c.timestamp < cfg.end));
+        data.insert(symbol.clone(), candles);
+    }
+    Ok(data)
+}
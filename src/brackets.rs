@@ -0,0 +1,346 @@
+// Bracket management for open positions: an initial stop (fixed % or ATRs
+// from entry), take-profit levels that each close part of the position, a
+// break-even move once the first target fills, and a trailing stop that
+// follows a percentage, ATRs or the EMA line. Brackets advance one closed
+// candle at a time; the stop only ever moves in the position's favor.
+use crate::delta::Candle;
+use crate::ema;
+use crate::sizing;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct TakeProfitLevel {
+    // Distance from entry, in %
+    pub distance_pct: f64,
+    // Share of the entry size closed at this level, in %
+    pub close_pct: f64,
+}
+
+impl Default for TakeProfitLevel {
+    fn default() -> Self {
+        Self { distance_pct: 3.0, close_pct: 100.0 }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct BracketConfig {
+    // "fixed" or "atr"
+    pub stop: String,
+    pub stop_pct: f64,
+    pub stop_atr_multiplier: f64,
+    pub atr_period: usize,
+    pub take_profits: Vec<TakeProfitLevel>,
+    // Move the stop to entry (plus the offset, in %) once the first target fills
+    pub break_even: bool,
+    pub break_even_offset_pct: f64,
+    // "none", "percent", "atr" or "ema"
+    pub trailing: String,
+    pub trail_pct: f64,
+    pub trail_atr_multiplier: f64,
+    pub trail_ema_period: usize,
+    // Only trail once the first target has filled
+    pub trail_after_first_target: bool,
+}
+
+impl Default for BracketConfig {
+    fn default() -> Self {
+        Self {
+            stop: "fixed".to_string(),
+            stop_pct: 1.5,
+            stop_atr_multiplier: 2.0,
+            atr_period: 14,
+            take_profits: vec![
+                TakeProfitLevel { distance_pct: 1.5, close_pct: 50.0 },
+                TakeProfitLevel { distance_pct: 3.0, close_pct: 50.0 },
+            ],
+            break_even: true,
+            break_even_offset_pct: 0.1,
+            trailing: "none".to_string(),
+            trail_pct: 1.0,
+            trail_atr_multiplier: 2.5,
+            trail_ema_period: 26,
+            trail_after_first_target: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Target {
+    pub price: f64,
+    // Contracts closed at this level
+    pub size: u64,
+    pub hit: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BracketEvent {
+    TargetHit(usize),
+    StopMoved(f64),
+    Stopped,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Bracket {
+    pub long: bool,
+    pub entry_price: f64,
+    pub stop_price: f64,
+    // Contracts still open
+    pub remaining: u64,
+    pub targets: Vec<Target>,
+    pub break_even_done: bool,
+    // Open time of the last candle applied
+    pub last_candle: u64,
+}
+
+// Stop level the trailing mode wants after `candles`, if it has one
+pub fn trail_level(cfg: &BracketConfig, long: bool, candles: &[Candle]) -> Option<f64> {
+    let close = candles.last()?.close;
+    let sign = if long { 1.0 } else { -1.0 };
+    let level = match cfg.trailing.as_str() {
+        "percent" => close * (1.0 - sign * cfg.trail_pct / 100.0),
+        "atr" => close - sign * sizing::latest_atr(candles, cfg.atr_period)? * cfg.trail_atr_multiplier,
+        "ema" => {
+            if candles.len() < cfg.trail_ema_period {
+                return None;
+            }
+            let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+            *ema::calculate_ema(&closes, cfg.trail_ema_period).last()?
+        }
+        _ => return None,
+    };
+    // A level on the wrong side of price would trigger at once
+    if (close - level) * sign > 0.0 { Some(level) } else { None }
+}
+
+// Stop for an entry at `entry`, before any break-even or trailing move
+pub fn initial_stop(cfg: &BracketConfig, long: bool, entry: f64, atr: Option<f64>) -> Result<f64, String> {
+    let sign = if long { 1.0 } else { -1.0 };
+    let stop = match cfg.stop.as_str() {
+        "atr" => entry - sign * atr.ok_or("not enough candles for an ATR stop")? * cfg.stop_atr_multiplier,
+        _ => entry * (1.0 - sign * cfg.stop_pct / 100.0),
+    };
+    if stop > 0.0 { Ok(stop) } else { Err(format!("stop {:.4} is not a valid price", stop)) }
+}
+
+impl Bracket {
+    // Initial stop and targets for an entry of `size` contracts at `entry`
+    pub fn new(cfg: &BracketConfig, long: bool, entry: f64, size: u64, atr: Option<f64>) -> Result<Self, String> {
+        let sign = if long { 1.0 } else { -1.0 };
+        let stop_price = initial_stop(cfg, long, entry, atr)?;
+        let mut targets = Vec::new();
+        let mut allocated = 0;
+        let total: f64 = cfg.take_profits.iter().map(|l| l.close_pct).sum();
+        for (i, level) in cfg.take_profits.iter().enumerate() {
+            let mut n = (size as f64 * level.close_pct / 100.0).floor() as u64;
+            // The last level takes any rounding remainder when the levels add up to the whole position
+            if i + 1 == cfg.take_profits.len() && total >= 100.0 {
+                n = size - allocated;
+            }
+            let n = n.min(size - allocated);
+            if n == 0 {
+                continue;
+            }
+            allocated += n;
+            targets.push(Target { price: entry * (1.0 + sign * level.distance_pct / 100.0), size: n, hit: false });
+        }
+        Ok(Self { long, entry_price: entry, stop_price, remaining: size, targets, break_even_done: false, last_candle: 0 })
+    }
+
+    fn favorable(&self, a: f64, b: f64) -> bool {
+        if self.long { a > b } else { a < b }
+    }
+
+    // Records a filled target; false if it was already counted
+    pub fn fill_target(&mut self, i: usize) -> bool {
+        match self.targets.get_mut(i) {
+            Some(t) if !t.hit => {
+                t.hit = true;
+                self.remaining = self.remaining.saturating_sub(t.size);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Shrinks the bracket to an entry of `size` contracts, taking the missing
+    // contracts off the last open targets first
+    pub fn resize(&mut self, size: u64) {
+        let closed: u64 = self.targets.iter().filter(|t| t.hit).map(|t| t.size).sum();
+        self.remaining = size.saturating_sub(closed);
+        let mut excess = self.targets.iter().filter(|t| !t.hit).map(|t| t.size).sum::<u64>().saturating_sub(self.remaining);
+        for t in self.targets.iter_mut().rev().filter(|t| !t.hit) {
+            let cut = excess.min(t.size);
+            t.size -= cut;
+            excess -= cut;
+        }
+    }
+
+    // Moves the bracket from the signal price to the actual entry fill: the
+    // stop and the open targets keep their distance from entry in %. A stop
+    // already moved to break-even is left alone.
+    pub fn reprice(&mut self, entry: f64) {
+        if entry <= 0.0 || self.entry_price <= 0.0 {
+            return;
+        }
+        let ratio = entry / self.entry_price;
+        self.entry_price = entry;
+        if !self.break_even_done {
+            self.stop_price *= ratio;
+        }
+        for t in self.targets.iter_mut().filter(|t| !t.hit) {
+            t.price *= ratio;
+        }
+    }
+
+    // Applies one closed candle with the trailing level computed through it.
+    // With `infer_fills`, stop and target fills are read from the candle's
+    // range (the stop first, so a candle that spans both counts as stopped);
+    // otherwise fills arrive from the exchange through fill_target.
+    pub fn on_candle(&mut self, cfg: &BracketConfig, candle: &Candle, trail: Option<f64>, infer_fills: bool) -> Vec<BracketEvent> {
+        let mut events = Vec::new();
+        if self.remaining == 0 || candle.timestamp <= self.last_candle {
+            return events;
+        }
+        self.last_candle = candle.timestamp;
+        if infer_fills {
+            let (adverse, favorable) = if self.long { (candle.low, candle.high) } else { (candle.high, candle.low) };
+            if !self.favorable(adverse, self.stop_price) {
+                self.remaining = 0;
+                events.push(BracketEvent::Stopped);
+                return events;
+            }
+            for i in 0..self.targets.len() {
+                let price = self.targets[i].price;
+                let reached = if self.long { favorable >= price } else { favorable <= price };
+                if reached && self.fill_target(i) {
+                    events.push(BracketEvent::TargetHit(i));
+                }
+            }
+            if self.remaining == 0 {
+                return events;
+            }
+        }
+        let first_hit = self.targets.first().is_some_and(|t| t.hit);
+        let mut stop = self.stop_price;
+        // Retried on later candles until price is far enough through entry
+        if cfg.break_even && first_hit && !self.break_even_done {
+            let sign = if self.long { 1.0 } else { -1.0 };
+            let be = self.entry_price * (1.0 + sign * cfg.break_even_offset_pct / 100.0);
+            if self.favorable(be, stop) && self.favorable(candle.close, be) {
+                stop = be;
+                self.break_even_done = true;
+            }
+        }
+        if let Some(level) = trail {
+            if (first_hit || !cfg.trail_after_first_target) && self.favorable(level, stop) {
+                stop = level;
+            }
+        }
+        if stop != self.stop_price {
+            self.stop_price = stop;
+            events.push(BracketEvent::StopMoved(stop));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(timestamp: u64, low: f64, high: f64, close: f64) -> Candle {
+        Candle { open: close, high, low, close, volume: 1.0, timestamp }
+    }
+
+    // Long at 100: stop 98.5, targets 101.5 (5 contracts) and 103 (5)
+    fn long() -> Bracket {
+        Bracket::new(&BracketConfig::default(), true, 100.0, 10, None).unwrap()
+    }
+
+    #[test]
+    fn new_splits_size_across_targets() {
+        let b = long();
+        assert!((b.stop_price - 98.5).abs() < 1e-9);
+        assert_eq!(b.targets.iter().map(|t| t.size).collect::<Vec<_>>(), vec![5, 5]);
+        // The last level takes the rounding remainder
+        let b = Bracket::new(&BracketConfig::default(), false, 100.0, 7, None).unwrap();
+        assert_eq!(b.targets.iter().map(|t| t.size).collect::<Vec<_>>(), vec![3, 4]);
+        assert!((b.targets[0].price - 98.5).abs() < 1e-9);
+        let atr = BracketConfig { stop: "atr".to_string(), ..BracketConfig::default() };
+        assert!(Bracket::new(&atr, true, 100.0, 10, None).is_err());
+    }
+
+    #[test]
+    fn inferred_fills_step_targets_and_break_even() {
+        let cfg = BracketConfig::default();
+        let mut b = long();
+        assert!(b.on_candle(&cfg, &candle(60, 99.0, 100.5, 100.2), None, true).is_empty());
+        // First target fills and the stop moves to entry plus the offset
+        let events = b.on_candle(&cfg, &candle(120, 100.0, 101.6, 101.2), None, true);
+        assert_eq!(events[0], BracketEvent::TargetHit(0));
+        assert!(matches!(events[1], BracketEvent::StopMoved(p) if (p - 100.1).abs() < 1e-9));
+        assert_eq!(b.remaining, 5);
+        // Candles already applied are skipped
+        assert!(b.on_candle(&cfg, &candle(120, 90.0, 110.0, 100.0), None, true).is_empty());
+        // A candle spanning both the stop and a target counts as stopped
+        assert_eq!(b.on_candle(&cfg, &candle(180, 100.0, 104.0, 102.0), None, true), vec![BracketEvent::Stopped]);
+        assert_eq!(b.remaining, 0);
+    }
+
+    #[test]
+    fn break_even_waits_for_price_through_entry() {
+        let cfg = BracketConfig::default();
+        let mut b = long();
+        b.fill_target(0);
+        // Closing below entry plus the offset leaves the stop where it is
+        assert!(b.on_candle(&cfg, &candle(60, 99.5, 100.5, 100.0), None, false).is_empty());
+        assert!(!b.break_even_done);
+        let events = b.on_candle(&cfg, &candle(120, 100.0, 101.0, 100.8), None, false);
+        assert_eq!(events.len(), 1);
+        assert!(b.break_even_done && (b.stop_price - 100.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn trailing_waits_for_first_target_and_never_loosens() {
+        let cfg = BracketConfig { trailing: "percent".to_string(), break_even: false, ..BracketConfig::default() };
+        let mut b = long();
+        let c = candle(60, 100.0, 101.0, 101.0);
+        assert!(b.on_candle(&cfg, &c, trail_level(&cfg, true, std::slice::from_ref(&c)), false).is_empty());
+        b.fill_target(0);
+        let c = candle(120, 101.0, 102.0, 102.0);
+        b.on_candle(&cfg, &c, trail_level(&cfg, true, std::slice::from_ref(&c)), false);
+        assert!((b.stop_price - 100.98).abs() < 1e-9);
+        let c = candle(180, 100.0, 101.0, 101.0);
+        assert!(b.on_candle(&cfg, &c, trail_level(&cfg, true, std::slice::from_ref(&c)), false).is_empty());
+        assert!((b.stop_price - 100.98).abs() < 1e-9);
+    }
+
+    #[test]
+    fn resize_takes_contracts_off_the_last_targets() {
+        let mut b = long();
+        b.resize(7);
+        assert_eq!(b.remaining, 7);
+        assert_eq!(b.targets.iter().map(|t| t.size).collect::<Vec<_>>(), vec![5, 2]);
+        b.resize(3);
+        assert_eq!(b.targets.iter().map(|t| t.size).collect::<Vec<_>>(), vec![3, 0]);
+        // Contracts already closed at a target stay counted
+        let mut b = long();
+        b.fill_target(0);
+        b.resize(8);
+        assert_eq!((b.remaining, b.targets[1].size), (3, 3));
+    }
+
+    #[test]
+    fn reprice_moves_stop_targets_and_break_even_to_the_fill() {
+        let cfg = BracketConfig::default();
+        let mut b = long();
+        b.reprice(102.0);
+        assert!((b.stop_price - 100.47).abs() < 1e-9);
+        assert!((b.targets[0].price - 103.53).abs() < 1e-9);
+        assert!((b.targets[1].price - 105.06).abs() < 1e-9);
+        // Break-even is taken from the fill, not the signal price
+        b.on_candle(&cfg, &candle(60, 103.0, 104.0, 103.8), None, true);
+        assert!((b.stop_price - 102.102).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,99 @@
+// Strategy configuration loaded from a TOML file (path in STRATEGY_CONFIG)
+//
+// Example:
+//   [[rules]]
+//   name = "1h trend entry"
+//   signal = "buy"
+//   when = "crosses_above(ema(12), ema(26)) on 1h and rsi(14) < 70"
+//
+//   [[scripts]]
+//   name = "mean reversion"
+//   path = "scripts/mean_reversion.rhai"
+//
+//   [scoring]
+//   min_score = 40
+//   [scoring.weights]
+//   trend_1h = 30
+use crate::backtest::BacktestConfig;
+use crate::execution::ExecutionConfig;
+use crate::history::HistoryConfig;
+use crate::montecarlo::MonteCarloConfig;
+use crate::optimize::OptimizeConfig;
+use crate::outcomes::OutcomeConfig;
+use crate::paper::PaperConfig;
+use crate::rules::RuleConfig;
+use crate::scoring::ScoringConfig;
+use crate::script::ScriptConfig;
+use crate::storage::StorageConfig;
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub struct StrategyConfig {
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    #[serde(default)]
+    pub scripts: Vec<ScriptConfig>,
+    #[serde(default)]
+    pub scoring: ScoringConfig,
+    #[serde(default)]
+    pub backtest: BacktestConfig,
+    #[serde(default)]
+    pub optimize: OptimizeConfig,
+    #[serde(default)]
+    pub montecarlo: MonteCarloConfig,
+    #[serde(default)]
+    pub outcomes: OutcomeConfig,
+    #[serde(default)]
+    pub paper: PaperConfig,
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+}
+
+pub fn load_strategy_config(path: &str) -> Result<StrategyConfig, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// Loads the file named by STRATEGY_CONFIG, or the defaults if unset
+pub fn load_from_env() -> Result<StrategyConfig, String> {
+    match std::env::var("STRATEGY_CONFIG") {
+        Ok(path) => load_strategy_config(&path),
+        Err(_) => Ok(StrategyConfig::default()),
+    }
+}
+
+// Raw TOML of the STRATEGY_CONFIG file (an empty table if unset), for tools
+// that override individual fields before deserializing
+pub fn load_raw_from_env() -> Result<toml::Value, String> {
+    match std::env::var("STRATEGY_CONFIG") {
+        Ok(path) => {
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+        }
+        Err(_) => Ok(toml::Value::Table(toml::map::Map::new())),
+    }
+}
+
+pub fn from_value(value: toml::Value) -> Result<StrategyConfig, String> {
+    value.try_into().map_err(|e: toml::de::Error| e.to_string())
+}
+
+// Sets a dotted path like "scoring.debounce.ema_fast", creating tables as needed.
+// Whole numbers are stored as integers so they can fill usize fields.
+pub fn set_path(root: &mut toml::Value, path: &str, value: f64) -> Result<(), String> {
+    let mut node = root;
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let table = node.as_table_mut().ok_or_else(|| format!("{}: '{}' is not a table", path, key))?;
+        if keys.peek().is_none() {
+            let v = if value.fract() == 0.0 { toml::Value::Integer(value as i64) } else { toml::Value::Float(value) };
+            table.insert(key.to_string(), v);
+            return Ok(());
+        }
+        node = table.entry(key.to_string()).or_insert_with(|| toml::Value::Table(toml::map::Map::new()));
+    }
+    Err(format!("empty parameter path '{}'", path))
+}
@@ -0,0 +1,75 @@
+// Crossover confirmation, minimum EMA separation and per-symbol cooldown
+use std::collections::HashMap;
+
+use crate::ema;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct DebounceConfig {
+    // EMA periods of the trend crossover
+    pub ema_fast: usize,
+    pub ema_slow: usize,
+    // Closed candles (including the crossing one) the EMA crossover must hold for
+    pub confirm_candles: usize,
+    // Minimum |fast EMA - slow EMA| as % of price on the last closed candle
+    pub min_separation_pct: f64,
+    // Minutes before the same symbol may signal the same direction again
+    pub cooldown_minutes: u64,
+}
+
+impl Default for DebounceConfig {
+    fn default() -> Self {
+        Self { ema_fast: 12, ema_slow: 26, confirm_candles: 1, min_separation_pct: 0.0, cooldown_minutes: 60 }
+    }
+}
+
+// Fast/slow EMA crossover that happened `confirm_candles` closed candles ago and
+// has stayed on the same side since. `closes` should end with the last closed
+// candle (or the forming one when previewing).
+pub fn confirmed_crossover(closes: &[f64], cfg: &DebounceConfig) -> Option<&'static str> {
+    let confirm = cfg.confirm_candles.max(1);
+    if closes.len() < cfg.ema_slow.max(cfg.ema_fast) + confirm {
+        return None;
+    }
+    let fast = ema::calculate_ema(closes, cfg.ema_fast);
+    let slow = ema::calculate_ema(closes, cfg.ema_slow);
+    let diff: Vec<f64> = fast.iter().zip(slow.iter()).map(|(a, b)| a - b).collect();
+    let n = diff.len();
+    let cross = n - confirm;
+    let signal = if diff[cross - 1] < 0.0 && diff[cross..].iter().all(|&d| d > 0.0) {
+        "buy"
+    } else if diff[cross - 1] > 0.0 && diff[cross..].iter().all(|&d| d < 0.0) {
+        "sell"
+    } else {
+        return None;
+    };
+    let price = closes[n - 1];
+    if price <= 0.0 || diff[n - 1].abs() / price * 100.0 < cfg.min_separation_pct {
+        return None;
+    }
+    Some(signal)
+}
+
+// Remembers when each symbol last signalled each direction
+#[derive(Default)]
+pub struct SignalGate {
+    last_emitted: HashMap<(String, &'static str), u64>,
+}
+
+impl SignalGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns true and records the signal if the symbol/direction is out of cooldown
+    pub fn allow(&mut self, symbol: &str, direction: &'static str, now: u64, cfg: &DebounceConfig) -> bool {
+        let key = (symbol.to_string(), direction);
+        if let Some(&last) = self.last_emitted.get(&key) {
+            if now.saturating_sub(last) < cfg.cooldown_minutes * 60 {
+                return false;
+            }
+        }
+        self.last_emitted.insert(key, now);
+        true
+    }
+}
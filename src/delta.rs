@@ -1,5 +1,4 @@
 use tokio_tungstenite::tungstenite::Message;
-use futures_util::{StreamExt, SinkExt};
 // OHLCV candle struct for chart matching
 #[derive(Clone, Debug)]
 pub struct Candle {
@@ -11,6 +10,19 @@ pub struct Candle {
     pub timestamp: u64, // Unix timestamp in seconds
 }
 
+// Parses a timeframe name like "5m", "4h" or "1d" into seconds
+pub fn timeframe_seconds(name: &str) -> Option<u64> {
+    let unit = name.chars().last()?;
+    let n: u64 = name[..name.len() - unit.len_utf8()].parse().ok()?;
+    let mult = match unit {
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => return None,
+    };
+    if n == 0 { None } else { Some(n * mult) }
+}
+
 // Aggregates trades into OHLCV candles for a given timeframe (in seconds)
 pub fn aggregate_candles(trades: &[(f64, f64, u64)], timeframe_sec: u64) -> Vec<Candle> {
     use std::collections::BTreeMap;
@@ -30,6 +42,92 @@ pub fn aggregate_candles(trades: &[(f64, f64, u64)], timeframe_sec: u64) -> Vec<
     }
     candles
 }
+// Splits aggregated candles into the closed ones and the bucket still forming at `now`
+pub fn split_forming(mut candles: Vec<Candle>, timeframe_sec: u64, now: u64) -> (Vec<Candle>, Option<Candle>) {
+    match candles.last() {
+        Some(last) if last.timestamp + timeframe_sec > now => {
+            let forming = candles.pop();
+            (candles, forming)
+        }
+        _ => (candles, None),
+    }
+}
+// Ticks carry the ticker's rolling 24h volume, not the traded size. The volume
+// traded between two ticks is its increase, clamped at 0 where the rolling
+// window drops more than was traded; the first tick has nothing to compare to.
+pub fn tick_volumes(trades: &[(f64, f64, u64)]) -> Vec<f64> {
+    let mut prev = None;
+    trades.iter().map(|t| {
+        let traded = prev.map_or(0.0, |p: f64| (t.1 - p).max(0.0));
+        prev = Some(t.1);
+        traded
+    }).collect()
+}
+// Top of book from the ticker; used to model fills
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Quote {
+    pub best_bid: f64,
+    pub best_ask: f64,
+    pub bid_size: f64,
+    pub ask_size: f64,
+}
+
+impl Quote {
+    // Parses the ticker's "quotes" object, whose numbers may arrive as strings
+    pub fn from_ticker(quotes: &serde_json::Value) -> Option<Self> {
+        let num = |k: &str| {
+            let v = quotes.get(k)?;
+            v.as_f64().or_else(|| v.as_str()?.parse().ok())
+        };
+        let q = Self {
+            best_bid: num("best_bid")?,
+            best_ask: num("best_ask")?,
+            bid_size: num("bid_size").unwrap_or(0.0),
+            ask_size: num("ask_size").unwrap_or(0.0),
+        };
+        if q.best_bid > 0.0 && q.best_ask >= q.best_bid { Some(q) } else { None }
+    }
+}
+// Contract specification needed to size and price orders
+#[derive(Clone, Debug)]
+pub struct Product {
+    pub id: u64,
+    pub symbol: String,
+    pub tick_size: f64,
+    // Underlying units per contract
+    pub contract_value: f64,
+}
+
+// Body of POST /v2/orders
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct OrderRequest {
+    pub product_id: u64,
+    pub product_symbol: String,
+    // Whole contracts
+    pub size: u64,
+    // "buy" or "sell"
+    pub side: String,
+    // "market_order" or "limit_order"
+    pub order_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<String>,
+    // "stop_loss_order" or "take_profit_order"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_order_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<String>,
+    pub reduce_only: bool,
+    pub client_order_id: String,
+}
+
+// Hex HMAC-SHA256 of method + timestamp + path + query + body, as Delta expects
+pub fn sign_request(secret: &str, method: &str, timestamp: &str, path: &str, query: &str, body: &str) -> String {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}{}{}{}{}", method, timestamp, path, query, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
 // Handles Delta Exchange API integration
 pub struct DeltaClient {
     pub api_key: String,
@@ -37,11 +135,10 @@ pub struct DeltaClient {
 }
 
 impl DeltaClient {
-    use tokio_tungstenite::tungstenite::Message;
     // Connects to Delta Exchange WebSocket for real-time price updates (5m base timeframe)
     pub async fn stream_realtime_prices<F>(&self, symbols: Vec<String>, mut on_price: F)
     where
-        F: FnMut(String, f64, f64) + Send + 'static,
+        F: FnMut(String, f64, f64, Option<Quote>) + Send + 'static,
     {
         use tokio_tungstenite::connect_async;
         use futures_util::{StreamExt, SinkExt};
@@ -83,7 +180,8 @@ impl DeltaClient {
                                                 // Too many events, skip this one
                                                 continue;
                                             }
-                                            on_price(symbol.to_string(), price, volume);
+                                            let quote = data.get("quotes").and_then(Quote::from_ticker);
+                                            on_price(symbol.to_string(), price, volume, quote);
                                         }
                                     }
                                 }
@@ -103,6 +201,53 @@ impl DeltaClient {
             }
         }
     }
+    // Authenticates on the WebSocket and forwards every update of the private
+    // "orders" channel; reconnects with backoff like the price stream
+    pub async fn stream_order_updates<F>(&self, mut on_order: F)
+    where
+        F: FnMut(serde_json::Value) + Send + 'static,
+    {
+        use tokio_tungstenite::connect_async;
+        use futures_util::{StreamExt, SinkExt};
+        use tokio::time::{sleep, Duration};
+        let mut backoff = 1;
+        loop {
+            match connect_async("wss://socket.delta.exchange").await {
+                Ok((ws_stream, _)) => {
+                    let (mut write, mut read) = ws_stream.split();
+                    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0).to_string();
+                    let auth = serde_json::json!({
+                        "type": "auth",
+                        "payload": {
+                            "api-key": self.api_key,
+                            "signature": sign_request(&self.api_secret, "GET", &timestamp, "/live", "", ""),
+                            "timestamp": timestamp,
+                        }
+                    });
+                    let sub = serde_json::json!({
+                        "type": "subscribe",
+                        "payload": {"channels": [{"name": "orders", "symbols": ["all"]}]}
+                    });
+                    let _ = write.send(Message::text(auth.to_string())).await;
+                    let _ = write.send(Message::text(sub.to_string())).await;
+                    backoff = 1;
+                    while let Some(msg) = read.next().await {
+                        if let Ok(Message::Text(txt)) = msg {
+                            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&txt) {
+                                if json.get("type").and_then(|t| t.as_str()) == Some("orders") {
+                                    on_order(json);
+                                }
+                            }
+                        }
+                    }
+                    eprintln!("Order stream disconnected, reconnecting in {}s...", backoff);
+                }
+                Err(e) => eprintln!("Order stream connection error: {}. Retrying in {}s...", e, backoff),
+            }
+            sleep(Duration::from_secs(backoff)).await;
+            backoff = (backoff * 2).min(32);
+        }
+    }
     pub fn new(api_key: String, api_secret: String) -> Self {
         Self { api_key, api_secret }
     }
@@ -129,4 +274,114 @@ impl DeltaClient {
         }
         Ok(markets)
     }
+    // Fetch historical OHLCV candles in [start, end), paging through the 2000-candle limit
+    pub async fn fetch_history_candles(&self, symbol: &str, resolution: &str, start: u64, end: u64) -> Result<Vec<Candle>, reqwest::Error> {
+        let url = "https://api.delta.exchange/v2/history/candles";
+        let client = reqwest::Client::new();
+        let step = timeframe_seconds(resolution).unwrap_or(300) * 2000;
+        let mut candles = Vec::new();
+        let mut from = start;
+        while from < end {
+            let to = (from + step).min(end);
+            let resp = client.get(url)
+                .query(&[("resolution", resolution.to_string()), ("symbol", symbol.to_string()), ("start", from.to_string()), ("end", to.to_string())])
+                .send()
+                .await?;
+            let json: serde_json::Value = resp.json().await?;
+            if let Some(rows) = json.get("result").and_then(|r| r.as_array()) {
+                for row in rows {
+                    let num = |k: &str| row.get(k).and_then(|v| v.as_f64());
+                    if let (Some(time), Some(open), Some(high), Some(low), Some(close)) = (num("time"), num("open"), num("high"), num("low"), num("close")) {
+                        let timestamp = time as u64;
+                        if timestamp >= from && timestamp < to {
+                            candles.push(Candle { open, high, low, close, volume: num("volume").unwrap_or(0.0), timestamp });
+                        }
+                    }
+                }
+            }
+            from = to;
+        }
+        candles.sort_by_key(|c| c.timestamp);
+        candles.dedup_by_key(|c| c.timestamp);
+        Ok(candles)
+    }
+    // Tick size and contract value of every live product
+    pub async fn fetch_products(&self) -> Result<Vec<Product>, reqwest::Error> {
+        let url = "https://api.delta.exchange/v2/products";
+        let json: serde_json::Value = reqwest::Client::new().get(url).send().await?.json().await?;
+        let mut products = Vec::new();
+        if let Some(rows) = json.get("result").and_then(|r| r.as_array()) {
+            for row in rows {
+                // Numeric fields may arrive as strings
+                let num = |k: &str| row.get(k).and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok()));
+                if let (Some(id), Some(symbol), Some(tick_size)) = (row.get("id").and_then(|v| v.as_u64()), row.get("symbol").and_then(|v| v.as_str()), num("tick_size")) {
+                    products.push(Product { id, symbol: symbol.to_string(), tick_size, contract_value: num("contract_value").unwrap_or(1.0) });
+                }
+            }
+        }
+        Ok(products)
+    }
+    // Sends an authenticated request and returns the response's "result"
+    async fn signed_request(&self, method: &str, path: &str, query: &str, body: Option<String>) -> Result<serde_json::Value, String> {
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs().to_string();
+        let body = body.unwrap_or_default();
+        let signature = sign_request(&self.api_secret, method, &timestamp, path, query, &body);
+        let url = format!("https://api.delta.exchange{}{}", path, query);
+        let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+        let resp = reqwest::Client::new().request(method, &url)
+            .header("api-key", &self.api_key)
+            .header("timestamp", timestamp)
+            .header("signature", signature)
+            .header("User-Agent", "ai_agent")
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+        if json.get("success").and_then(|v| v.as_bool()) == Some(true) {
+            Ok(json.get("result").cloned().unwrap_or(serde_json::Value::Null))
+        } else {
+            Err(json.get("error").map_or_else(|| json.to_string(), |e| e.to_string()))
+        }
+    }
+    // Balance of one wallet asset
+    pub async fn fetch_wallet_balance(&self, asset: &str) -> Result<f64, String> {
+        let result = self.signed_request("GET", "/v2/wallet/balances", "", None).await?;
+        result.as_array().into_iter().flatten()
+            .find(|w| w.get("asset_symbol").and_then(|v| v.as_str()) == Some(asset))
+            .and_then(|w| w.get("balance").and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok())))
+            .ok_or_else(|| format!("no {} wallet", asset))
+    }
+    // Open and untriggered orders
+    pub async fn fetch_open_orders(&self) -> Result<Vec<serde_json::Value>, String> {
+        let result = self.signed_request("GET", "/v2/orders", "?states=open,pending", None).await?;
+        Ok(result.as_array().cloned().unwrap_or_default())
+    }
+    pub async fn fetch_order(&self, order_id: u64) -> Result<serde_json::Value, String> {
+        self.signed_request("GET", &format!("/v2/orders/{}", order_id), "", None).await
+    }
+    // Signed contract size of every open position by symbol (negative for shorts)
+    pub async fn fetch_positions(&self) -> Result<std::collections::HashMap<String, i64>, String> {
+        let result = self.signed_request("GET", "/v2/positions/margined", "", None).await?;
+        Ok(result.as_array().into_iter().flatten()
+            .filter_map(|p| Some((p.get("product_symbol")?.as_str()?.to_string(), p.get("size")?.as_i64()?)))
+            .filter(|(_, size)| *size != 0)
+            .collect())
+    }
+    // Places an order and returns the exchange order id
+    pub async fn place_order(&self, order: &OrderRequest) -> Result<u64, String> {
+        let body = serde_json::to_string(order).map_err(|e| e.to_string())?;
+        let result = self.signed_request("POST", "/v2/orders", "", Some(body)).await?;
+        result.get("id").and_then(|v| v.as_u64()).ok_or_else(|| format!("order response without id: {}", result))
+    }
+    // Changes the size and trigger price of an open stop order
+    pub async fn edit_order(&self, product_id: u64, order_id: u64, size: u64, stop_price: &str) -> Result<(), String> {
+        let body = serde_json::json!({ "id": order_id, "product_id": product_id, "size": size, "stop_price": stop_price }).to_string();
+        self.signed_request("PUT", "/v2/orders", "", Some(body)).await.map(|_| ())
+    }
+    pub async fn cancel_order(&self, product_id: u64, order_id: u64) -> Result<(), String> {
+        let body = serde_json::json!({ "id": order_id, "product_id": product_id }).to_string();
+        self.signed_request("DELETE", "/v2/orders", "", Some(body)).await.map(|_| ())
+    }
 }
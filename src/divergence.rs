@@ -0,0 +1,117 @@
+// Swing point detection and price/oscillator divergences
+use crate::delta::Candle;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum SwingKind {
+    High,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct Swing {
+    pub index: usize,
+    pub kind: SwingKind,
+    pub price: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum DivergenceKind {
+    RegularBullish, // price lower low, oscillator higher low
+    HiddenBullish,  // price higher low, oscillator lower low
+    RegularBearish, // price higher high, oscillator lower high
+    HiddenBearish,  // price lower high, oscillator higher high
+}
+
+impl DivergenceKind {
+    pub fn signal(&self) -> &'static str {
+        match self {
+            DivergenceKind::RegularBullish | DivergenceKind::HiddenBullish => "buy",
+            DivergenceKind::RegularBearish | DivergenceKind::HiddenBearish => "sell",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct Divergence {
+    pub kind: DivergenceKind,
+    pub from: Swing,
+    pub to: Swing,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct DivergenceConfig {
+    // Candles on each side a swing must exceed
+    pub swing_lookback: usize,
+    // Max candles between the two swings being compared
+    pub max_span: usize,
+    // Max candles from the latest swing to the last candle for it to count
+    pub max_age: usize,
+}
+
+impl Default for DivergenceConfig {
+    fn default() -> Self {
+        Self { swing_lookback: 3, max_span: 60, max_age: 8 }
+    }
+}
+
+// Swing highs/lows: the candle's high (low) is strictly above (below) the
+// `lookback` candles on either side
+pub fn find_swings(candles: &[Candle], lookback: usize) -> Vec<Swing> {
+    let mut swings = Vec::new();
+    if lookback == 0 || candles.len() < 2 * lookback + 1 {
+        return swings;
+    }
+    for i in lookback..candles.len() - lookback {
+        let window = candles[i - lookback..=i + lookback].iter().enumerate().filter(|(j, _)| *j != lookback);
+        let c = &candles[i];
+        if window.clone().all(|(_, o)| c.high > o.high) {
+            swings.push(Swing { index: i, kind: SwingKind::High, price: c.high });
+        }
+        if window.clone().all(|(_, o)| c.low < o.low) {
+            swings.push(Swing { index: i, kind: SwingKind::Low, price: c.low });
+        }
+    }
+    swings
+}
+
+// Compares consecutive swings of the same kind against an oscillator aligned
+// with the candles (e.g. ema::calculate_rsi or ema::calculate_macd_histogram)
+pub fn detect_divergences(candles: &[Candle], oscillator: &[f64], config: &DivergenceConfig) -> Vec<Divergence> {
+    let mut out = Vec::new();
+    if oscillator.len() != candles.len() {
+        return out;
+    }
+    let swings = find_swings(candles, config.swing_lookback);
+    for kind in [SwingKind::High, SwingKind::Low] {
+        let same: Vec<&Swing> = swings.iter().filter(|s| s.kind == kind).collect();
+        for pair in same.windows(2) {
+            let (a, b) = (*pair[0], *pair[1]);
+            if b.index - a.index > config.max_span {
+                continue;
+            }
+            let (oa, ob) = (oscillator[a.index], oscillator[b.index]);
+            let div = match kind {
+                SwingKind::Low if b.price < a.price && ob > oa => Some(DivergenceKind::RegularBullish),
+                SwingKind::Low if b.price > a.price && ob < oa => Some(DivergenceKind::HiddenBullish),
+                SwingKind::High if b.price > a.price && ob < oa => Some(DivergenceKind::RegularBearish),
+                SwingKind::High if b.price < a.price && ob > oa => Some(DivergenceKind::HiddenBearish),
+                _ => None,
+            };
+            if let Some(kind) = div {
+                out.push(Divergence { kind, from: a, to: b });
+            }
+        }
+    }
+    out.sort_by_key(|d| d.to.index);
+    out
+}
+
+// Most recent divergence that is still fresh enough to act on
+pub fn latest_divergence(candles: &[Candle], oscillator: &[f64], config: &DivergenceConfig) -> Option<Divergence> {
+    let last = candles.len().checked_sub(1)?;
+    detect_divergences(candles, oscillator, config)
+        .into_iter()
+        .rev()
+        .find(|d| last - d.to.index <= config.swing_lookback + config.max_age)
+}
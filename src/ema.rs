@@ -12,64 +12,259 @@ pub fn calculate_ema(prices: &[f64], period: usize) -> Vec<f64> {
     ema
 }
 
-// Detects buy/sell signals based on EMA 12/26 crossover
-pub fn detect_ema_signals(prices: &[f64]) -> Option<&'static str> {
-    if prices.len() < 26 {
+// Simple moving average, same length as prices (warmup uses the running mean)
+pub fn calculate_sma(prices: &[f64], period: usize) -> Vec<f64> {
+    let mut sma = Vec::with_capacity(prices.len());
+    let mut sum = 0.0;
+    for (i, &price) in prices.iter().enumerate() {
+        sum += price;
+        if i >= period {
+            sum -= prices[i - period];
+        }
+        sma.push(sum / (i + 1).min(period) as f64);
+    }
+    sma
+}
+
+// Wilder RSI, same length as prices (warmup values are a neutral 50)
+pub fn calculate_rsi(prices: &[f64], period: usize) -> Vec<f64> {
+    let mut rsi = vec![50.0; prices.len()];
+    if period == 0 || prices.len() <= period {
+        return rsi;
+    }
+    let mut avg_gain = 0.0;
+    let mut avg_loss = 0.0;
+    for i in 1..=period {
+        let change = prices[i] - prices[i - 1];
+        if change > 0.0 { avg_gain += change; } else { avg_loss -= change; }
+    }
+    avg_gain /= period as f64;
+    avg_loss /= period as f64;
+    let to_rsi = |gain: f64, loss: f64| if loss == 0.0 { 100.0 } else { 100.0 - 100.0 / (1.0 + gain / loss) };
+    rsi[period] = to_rsi(avg_gain, avg_loss);
+    for i in (period + 1)..prices.len() {
+        let change = prices[i] - prices[i - 1];
+        let (gain, loss) = if change > 0.0 { (change, 0.0) } else { (0.0, -change) };
+        avg_gain = (avg_gain * (period as f64 - 1.0) + gain) / period as f64;
+        avg_loss = (avg_loss * (period as f64 - 1.0) + loss) / period as f64;
+        rsi[i] = to_rsi(avg_gain, avg_loss);
+    }
+    rsi
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct MacdConfig {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl Default for MacdConfig {
+    fn default() -> Self {
+        Self { fast: 12, slow: 26, signal: 9 }
+    }
+}
+
+// MACD line and signal line (12/26/9)
+pub fn calculate_macd(prices: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    calculate_macd_with(prices, &MacdConfig::default())
+}
+
+// MACD line and signal line for custom periods
+pub fn calculate_macd_with(prices: &[f64], cfg: &MacdConfig) -> (Vec<f64>, Vec<f64>) {
+    let fast = calculate_ema(prices, cfg.fast);
+    let slow = calculate_ema(prices, cfg.slow);
+    let macd: Vec<f64> = fast.iter().zip(slow.iter()).map(|(f, s)| f - s).collect();
+    let signal = calculate_ema(&macd, cfg.signal);
+    (macd, signal)
+}
+
+// MACD histogram (MACD minus signal line)
+pub fn calculate_macd_histogram(prices: &[f64], cfg: &MacdConfig) -> Vec<f64> {
+    let (macd, signal) = calculate_macd_with(prices, cfg);
+    macd.iter().zip(signal.iter()).map(|(m, s)| m - s).collect()
+}
+
+// Wilder smoothing of a series, same length as values
+fn wilder_smooth(values: &[f64], period: usize) -> Vec<f64> {
+    let mut out = vec![0.0; values.len()];
+    if period == 0 || values.len() < period {
+        return out;
+    }
+    let mut avg = values[..period].iter().sum::<f64>() / period as f64;
+    out[period - 1] = avg;
+    for i in period..values.len() {
+        avg = (avg * (period as f64 - 1.0) + values[i]) / period as f64;
+        out[i] = avg;
+    }
+    out
+}
+
+// Average true range (Wilder), same length as the inputs
+pub fn calculate_atr(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> Vec<f64> {
+    let tr: Vec<f64> = (0..closes.len()).map(|i| {
+        let range = highs[i] - lows[i];
+        if i == 0 {
+            range
+        } else {
+            range.max((highs[i] - closes[i - 1]).abs()).max((lows[i] - closes[i - 1]).abs())
+        }
+    }).collect();
+    wilder_smooth(&tr, period)
+}
+
+// Average directional index (Wilder), same length as the inputs; warmup values are 0
+pub fn calculate_adx(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> Vec<f64> {
+    let n = closes.len();
+    let mut plus_dm = vec![0.0; n];
+    let mut minus_dm = vec![0.0; n];
+    for i in 1..n {
+        let up = highs[i] - highs[i - 1];
+        let down = lows[i - 1] - lows[i];
+        if up > down && up > 0.0 { plus_dm[i] = up; }
+        if down > up && down > 0.0 { minus_dm[i] = down; }
+    }
+    let atr = calculate_atr(highs, lows, closes, period);
+    let plus = wilder_smooth(&plus_dm, period);
+    let minus = wilder_smooth(&minus_dm, period);
+    let dx: Vec<f64> = (0..n).map(|i| {
+        if atr[i] <= 0.0 { return 0.0; }
+        let (pdi, mdi) = (100.0 * plus[i] / atr[i], 100.0 * minus[i] / atr[i]);
+        if pdi + mdi > 0.0 { 100.0 * (pdi - mdi).abs() / (pdi + mdi) } else { 0.0 }
+    }).collect();
+    if period == 0 || n < 2 * period {
+        return vec![0.0; n];
+    }
+    let mut adx = vec![0.0; n];
+    let smoothed = wilder_smooth(&dx[period - 1..], period);
+    adx[period - 1..].copy_from_slice(&smoothed);
+    adx
+}
+
+// MACD crossover detection
+pub fn detect_macd_crossover(prices: &[f64], cfg: &MacdConfig) -> Option<&'static str> {
+    if prices.len() < cfg.slow.max(cfg.fast) + cfg.signal {
         return None;
     }
-    let ema12 = calculate_ema(prices, 12);
-    let ema26 = calculate_ema(prices, 26);
-    let last_ema12 = ema12.last()?;
-    let last_ema26 = ema26.last()?;
-    let prev_ema12 = ema12.get(ema12.len().saturating_sub(2))?;
-    let prev_ema26 = ema26.get(ema26.len().saturating_sub(2))?;
-    if prev_ema12 < prev_ema26 && last_ema12 > last_ema26 {
+    let (macd, signal) = calculate_macd_with(prices, cfg);
+    let last_macd = macd.last()?;
+    let last_signal = signal.last()?;
+    let prev_macd = macd.get(macd.len().saturating_sub(2))?;
+    let prev_signal = signal.get(signal.len().saturating_sub(2))?;
+    if prev_macd < prev_signal && last_macd > last_signal {
         Some("buy")
-    } else if prev_ema12 > prev_ema26 && last_ema12 < last_ema26 {
+    } else if prev_macd > prev_signal && last_macd < last_signal {
         Some("sell")
     } else {
         None
     }
 }
 
-// Points-based signal logic across 5 timeframes
-pub fn points_based_signal(timeframe_signals: &[Option<&str>]) -> Option<&'static str> {
-    // 60 points threshold, 5 timeframes, 12 points each
-    let points_per_tf = 12;
-    let mut buy_points = 0;
-    let mut sell_points = 0;
-    for signal in timeframe_signals {
-        match signal {
-            Some("buy") => buy_points += points_per_tf,
-            Some("sell") => sell_points += points_per_tf,
-            _ => {}
-        }
+// Ichimoku Kinko Hyo lines. Senkou spans are displaced forward, so they have
+// `displacement` more entries than the inputs; index i is the cloud over candle i.
+// Chikou is the close displaced back: chikou[i] is the close of candle i + displacement.
+pub struct Ichimoku {
+    pub tenkan: Vec<f64>,
+    pub kijun: Vec<f64>,
+    pub senkou_a: Vec<f64>,
+    pub senkou_b: Vec<f64>,
+    pub chikou: Vec<f64>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct IchimokuConfig {
+    pub tenkan: usize,
+    pub kijun: usize,
+    pub senkou_b: usize,
+    pub displacement: usize,
+}
+
+impl Default for IchimokuConfig {
+    fn default() -> Self {
+        Self { tenkan: 9, kijun: 26, senkou_b: 52, displacement: 26 }
+    }
+}
+
+// Midpoint of the highest high and lowest low over `period` candles ending at each index
+fn donchian_mid(highs: &[f64], lows: &[f64], period: usize) -> Vec<f64> {
+    (0..highs.len()).map(|i| {
+        let start = (i + 1).saturating_sub(period.max(1));
+        let hh = highs[start..=i].iter().cloned().fold(f64::MIN, f64::max);
+        let ll = lows[start..=i].iter().cloned().fold(f64::MAX, f64::min);
+        (hh + ll) / 2.0
+    }).collect()
+}
+
+pub fn calculate_ichimoku(highs: &[f64], lows: &[f64], closes: &[f64], cfg: &IchimokuConfig) -> Ichimoku {
+    let tenkan = donchian_mid(highs, lows, cfg.tenkan);
+    let kijun = donchian_mid(highs, lows, cfg.kijun);
+    let span_b = donchian_mid(highs, lows, cfg.senkou_b);
+    let n = highs.len();
+    let first = |v: &[f64]| v.first().cloned().unwrap_or(0.0);
+    // Before the first projected value the cloud is held at the earliest one
+    let mut senkou_a = vec![(first(&tenkan) + first(&kijun)) / 2.0; cfg.displacement];
+    senkou_a.extend(tenkan.iter().zip(kijun.iter()).map(|(t, k)| (t + k) / 2.0));
+    let mut senkou_b = vec![first(&span_b); cfg.displacement];
+    senkou_b.extend(span_b);
+    let chikou = closes.iter().skip(cfg.displacement).cloned().collect();
+    debug_assert_eq!(senkou_a.len(), n + cfg.displacement);
+    Ichimoku { tenkan, kijun, senkou_a, senkou_b, chikou }
+}
+
+// Enough candles for the displaced Senkou B to cover the last two
+pub fn ichimoku_ready(closes: &[f64], cfg: &IchimokuConfig) -> bool {
+    closes.len() > cfg.senkou_b.max(cfg.kijun) + cfg.displacement
+}
+
+// Tenkan/Kijun cross
+pub fn detect_tk_cross(ich: &Ichimoku) -> Option<&'static str> {
+    let n = ich.tenkan.len();
+    if n < 2 {
+        return None;
     }
-    if buy_points >= 60 {
+    let (pt, pk, lt, lk) = (ich.tenkan[n - 2], ich.kijun[n - 2], ich.tenkan[n - 1], ich.kijun[n - 1]);
+    if pt <= pk && lt > lk {
         Some("buy")
-    } else if sell_points >= 60 {
+    } else if pt >= pk && lt < lk {
         Some("sell")
     } else {
         None
     }
 }
 
-// MACD crossover detection
-pub fn detect_macd_crossover(prices: &[f64]) -> Option<&'static str> {
-    if prices.len() < 35 {
+// Close breaking out above the cloud top or below the cloud bottom, confirmed
+// by the Chikou being on the same side of the price it is displaced against
+pub fn detect_kumo_breakout(closes: &[f64], ich: &Ichimoku) -> Option<&'static str> {
+    let n = closes.len();
+    let m = ich.chikou.len();
+    if n < 2 || m == 0 || ich.senkou_a.len() < n {
         return None;
     }
-    let ema12 = calculate_ema(prices, 12);
-    let ema26 = calculate_ema(prices, 26);
-    let macd: Vec<f64> = ema12.iter().zip(ema26.iter()).map(|(e12, e26)| e12 - e26).collect();
-    let signal = calculate_ema(&macd, 9);
-    let last_macd = macd.last()?;
-    let last_signal = signal.last()?;
-    let prev_macd = macd.get(macd.len().saturating_sub(2))?;
-    let prev_signal = signal.get(signal.len().saturating_sub(2))?;
-    if prev_macd < prev_signal && last_macd > last_signal {
+    let top = |i: usize| ich.senkou_a[i].max(ich.senkou_b[i]);
+    let bottom = |i: usize| ich.senkou_a[i].min(ich.senkou_b[i]);
+    let chikou = ich.chikou[m - 1] - closes[m - 1];
+    if closes[n - 2] <= top(n - 2) && closes[n - 1] > top(n - 1) && chikou > 0.0 {
         Some("buy")
-    } else if prev_macd > prev_signal && last_macd < last_signal {
+    } else if closes[n - 2] >= bottom(n - 2) && closes[n - 1] < bottom(n - 1) && chikou < 0.0 {
+        Some("sell")
+    } else {
+        None
+    }
+}
+
+// Senkou A crossing Senkou B at the leading (displaced) edge of the cloud
+pub fn detect_kumo_twist(ich: &Ichimoku) -> Option<&'static str> {
+    let m = ich.senkou_a.len();
+    if m < 2 {
+        return None;
+    }
+    let prev = ich.senkou_a[m - 2] - ich.senkou_b[m - 2];
+    let last = ich.senkou_a[m - 1] - ich.senkou_b[m - 1];
+    if prev <= 0.0 && last > 0.0 {
+        Some("buy")
+    } else if prev >= 0.0 && last < 0.0 {
         Some("sell")
     } else {
         None
@@ -0,0 +1,797 @@
+// Live order execution: turns pipeline signals into Delta orders
+//
+// A closed-candle signal on an allowlisted symbol places a market entry, then a
+// reduce-only stop-loss and one take-profit per bracket level. On each candle
+// close the bracket is advanced and the stop amended on the exchange when it
+// moves (see brackets.rs). Sizes come from the configured sizing
+// model in whole contracts, and trigger prices are rounded to the product's
+// tick size. Client order ids are derived from the signal (symbol, timeframe,
+// direction and candle), and ids already sent are persisted, so a signal
+// replayed after a reconnect or restart does not enter twice. Every entry
+// passes the risk manager first (see risk.rs). Sent orders are tracked through
+// their lifecycle (see orders.rs); their fills drive the brackets (an entry
+// that fills only in part shrinks its stop and targets), and a
+// periodic reconciliation against the exchange's positions and open orders
+// fixes drift. With dry_run set, orders are logged instead of sent, fills
+// are inferred from candles and the state goes to its own file.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use log::info;
+
+use crate::brackets::{self, Bracket, BracketConfig, BracketEvent};
+use crate::delta::{Candle, DeltaClient, OrderRequest, Product};
+use crate::orders::{OrderState, OrderTracker, OrderUpdate, TrackedOrder};
+use crate::pipeline::PipelineSignal;
+use crate::risk::{Exposure, KillSwitchChange, RiskConfig, RiskManager, RiskSnapshot, RiskState};
+use crate::sizing::{self, SizingConfig, SizingInput};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct ExecutionConfig {
+    pub enabled: bool,
+    // Log intended orders without sending them
+    pub dry_run: bool,
+    // Symbols allowed to trade; empty allows none
+    pub symbols: Vec<String>,
+    pub sizing: SizingConfig,
+    // Wallet asset whose balance is the equity for sizing
+    pub settlement_asset: String,
+    pub allow_short: bool,
+    pub bracket: BracketConfig,
+    pub risk: RiskConfig,
+    pub state_path: String,
+    // Dry-run positions are kept apart so they never mix with live ones
+    pub dry_run_state_path: String,
+    // Client order ids remembered for deduplication
+    pub max_client_ids: usize,
+    // Finished orders kept in the lifecycle history
+    pub max_tracked_orders: usize,
+    pub reconcile_interval_secs: u64,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dry_run: true,
+            symbols: Vec::new(),
+            sizing: SizingConfig::default(),
+            settlement_asset: "USD".to_string(),
+            allow_short: true,
+            bracket: BracketConfig::default(),
+            risk: RiskConfig::default(),
+            state_path: "execution_state.json".to_string(),
+            dry_run_state_path: "execution_state.dry_run.json".to_string(),
+            max_client_ids: 1000,
+            max_tracked_orders: 500,
+            reconcile_interval_secs: 900,
+        }
+    }
+}
+
+impl ExecutionConfig {
+    // State file for the current mode
+    pub fn state_path(&self) -> &str {
+        if self.dry_run { &self.dry_run_state_path } else { &self.state_path }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LivePosition {
+    pub symbol: String,
+    pub timeframe: String,
+    pub product_id: u64,
+    // "long" or "short"
+    pub side: String,
+    // Contracts entered; the bracket tracks what is still open
+    pub size: u64,
+    pub entry_price: f64,
+    pub entry_time: u64,
+    pub client_order_id: String,
+    // Exchange ids of the protective orders (None in dry run or if placing failed)
+    pub stop_order_id: Option<u64>,
+    // One per bracket target
+    pub take_profit_order_ids: Vec<Option<u64>>,
+    pub bracket: Bracket,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ExecutionState {
+    // Client order ids already sent, oldest first
+    pub sent: VecDeque<String>,
+    pub positions: BTreeMap<String, LivePosition>,
+    #[serde(default)]
+    pub risk: RiskState,
+    #[serde(default)]
+    pub orders: OrderTracker,
+}
+
+// Orders for one signal
+#[derive(Clone, Debug)]
+pub struct OrderPlan {
+    pub entry: OrderRequest,
+    pub stop_loss: OrderRequest,
+    pub take_profits: Vec<OrderRequest>,
+    pub bracket: Bracket,
+}
+
+// Rounds to the nearest multiple of the tick size
+pub fn round_to_tick(price: f64, tick: f64) -> f64 {
+    if tick > 0.0 { (price / tick).round() * tick } else { price }
+}
+
+// Price string with as many decimals as the tick size has
+pub fn format_price(price: f64, tick: f64) -> String {
+    let decimals = if tick > 0.0 && tick < 1.0 { (-tick.log10()).ceil() as usize } else { 0 };
+    format!("{:.*}", decimals, round_to_tick(price, tick))
+}
+
+// Deterministic id for a signal: the same candle and direction always map to
+// the same id, however late the cycle that acts on it runs. Delta limits
+// client order ids to 32 characters.
+pub fn client_order_id(sig: &PipelineSignal, suffix: &str) -> String {
+    let candle = sig.candle_time;
+    let symbol: String = sig.symbol.chars().take(10).collect();
+    let dir = if sig.direction == "buy" { "b" } else { "s" };
+    let id = format!("{}-{}-{}-{}-{}", symbol, sig.timeframe, dir, candle, suffix);
+    id.chars().take(32).collect()
+}
+
+// The orders of one signal share their client id up to the suffix: the
+// entry's "e", the stop's "sl" and the take-profits' "tp<n>"
+fn signal_key(client_order_id: &str) -> &str {
+    client_order_id.rsplit_once('-').map_or(client_order_id, |(key, _)| key)
+}
+
+fn order(product: &Product, size: u64, side: &str, client_order_id: String) -> OrderRequest {
+    OrderRequest {
+        product_id: product.id,
+        product_symbol: product.symbol.clone(),
+        size,
+        side: side.to_string(),
+        order_type: "market_order".to_string(),
+        limit_price: None,
+        stop_order_type: None,
+        stop_price: None,
+        reduce_only: false,
+        client_order_id,
+    }
+}
+
+pub struct Executor {
+    pub cfg: ExecutionConfig,
+    products: HashMap<String, Product>,
+    state: ExecutionState,
+    // Account equity used for sizing, refreshed from the wallet
+    equity: f64,
+    pub risk: RiskManager,
+}
+
+impl Executor {
+    // Resumes from the state file, or starts with no positions
+    pub fn load(cfg: ExecutionConfig, products: Vec<Product>) -> Result<Self, String> {
+        let path = cfg.state_path().to_string();
+        let mut state: ExecutionState = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ExecutionState::default(),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+        let products = products.into_iter().map(|p| (p.symbol.clone(), p)).collect();
+        let risk = RiskManager::new(cfg.risk.clone(), std::mem::take(&mut state.risk));
+        Ok(Self { cfg, products, state, equity: 0.0, risk })
+    }
+
+    pub fn orders(&self) -> &OrderTracker {
+        &self.state.orders
+    }
+
+    // Picks up unfinished orders the state file lost; polling then brings them up to date
+    pub fn restore_orders(&mut self, orders: Vec<TrackedOrder>) {
+        for order in orders {
+            self.state.orders.restore(order);
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let mut state = self.state.clone();
+        state.risk = self.risk.state.clone();
+        let json = serde_json::to_string_pretty(&state).map_err(|e| e.to_string())?;
+        std::fs::write(self.cfg.state_path(), json).map_err(|e| format!("{}: {}", self.cfg.state_path(), e))
+    }
+
+    // Refreshes equity (the margin balance: wallet plus unrealized PnL); when
+    // the daily loss trips the kill switch, cancels working entries and
+    // flattens if configured
+    pub async fn update_equity(&mut self, client: &DeltaClient, equity: f64, now: u64) -> Result<Option<KillSwitchChange>, String> {
+        self.equity = equity;
+        let change = self.risk.update_equity(equity, now);
+        if let Some(KillSwitchChange::Tripped(kill)) = &change {
+            info!("Kill switch tripped: {}", kill.reason);
+            self.kill(client, now).await?;
+        }
+        Ok(change)
+    }
+
+    // Unrealized PnL of the open positions at `prices` (latest trade per symbol)
+    pub fn unrealized_pnl(&self, prices: &HashMap<String, f64>) -> f64 {
+        self.state.positions.values().map(|p| {
+            let price = prices.get(&p.symbol).copied().unwrap_or(p.entry_price);
+            let value = self.products.get(&p.symbol).map_or(1.0, |pr| pr.contract_value);
+            let sign = if p.side == "long" { 1.0 } else { -1.0 };
+            (price - p.entry_price) * sign * p.bracket.remaining as f64 * value
+        }).sum()
+    }
+
+    // Cancels entries still working; stops and take-profits stay so open
+    // positions remain protected unless they are flattened
+    async fn kill(&mut self, client: &DeltaClient, now: u64) -> Result<(), String> {
+        let entries: Vec<(String, u64, u64)> = self.state.orders.live()
+            .filter(|o| o.role == "entry")
+            .filter_map(|o| Some((o.symbol.clone(), o.product_id, o.exchange_id?)))
+            .collect();
+        for (symbol, product_id, id) in entries {
+            client.cancel_order(product_id, id).await.map_err(|e| format!("{}: cancel entry {}: {}", symbol, id, e))?;
+            info!("{}: entry {} cancelled by the kill switch", symbol, id);
+        }
+        if self.cfg.risk.flatten_on_kill {
+            let symbols: Vec<String> = self.state.positions.keys().cloned().collect();
+            for symbol in symbols {
+                let id = format!("{}-kill-{}", symbol.chars().take(10).collect::<String>(), now);
+                self.close(client, &symbol, id, now).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Signed notional of each open position at the latest close
+    pub fn exposures(&self) -> Vec<Exposure> {
+        self.state.positions.values().map(|p| {
+            let price = self.risk.last_close(&p.symbol).unwrap_or(p.entry_price);
+            let value = self.products.get(&p.symbol).map_or(1.0, |pr| pr.contract_value);
+            let notional = p.bracket.remaining as f64 * price * value;
+            Exposure { symbol: p.symbol.clone(), notional: if p.side == "long" { notional } else { -notional } }
+        }).collect()
+    }
+
+    pub fn risk_snapshot(&self) -> RiskSnapshot {
+        self.risk.snapshot(&self.exposures())
+    }
+
+    fn allowed(&self, symbol: &str) -> bool {
+        self.cfg.symbols.iter().any(|s| s == symbol)
+    }
+
+    fn remember(&mut self, id: &str) {
+        self.state.sent.push_back(id.to_string());
+        while self.state.sent.len() > self.cfg.max_client_ids {
+            self.state.sent.pop_front();
+        }
+    }
+
+    // Entry, stop-loss and take-profit for a signal; `candles` are the signal
+    // timeframe's closed candles
+    pub fn plan(&self, sig: &PipelineSignal, product: &Product, candles: &[Candle]) -> Result<OrderPlan, String> {
+        if self.equity <= 0.0 {
+            return Err(format!("{}: account equity unknown", sig.symbol));
+        }
+        let long = sig.direction == "buy";
+        let exit_side = if long { "sell" } else { "buy" };
+        let stop_atr = sizing::latest_atr(candles, self.cfg.bracket.atr_period);
+        let stop_price = brackets::initial_stop(&self.cfg.bracket, long, sig.price, stop_atr).map_err(|e| format!("{}: {}", sig.symbol, e))?;
+        // The rule or script that raised the signal may size its own entries
+        let sizing_cfg = sig.sizing.as_ref().unwrap_or(&self.cfg.sizing);
+        let input = SizingInput {
+            equity: self.equity,
+            price: sig.price,
+            stop_price: Some(stop_price),
+            atr: sizing::latest_atr(candles, sizing_cfg.atr_period),
+            contract_value: product.contract_value,
+        };
+        let size = sizing::contracts(sizing_cfg, &input).map_err(|e| format!("{}: {}", sig.symbol, e))?;
+        if size == 0 {
+            return Err(format!("{}: {} sizing is below one contract", sig.symbol, sizing_cfg.model));
+        }
+        let mut bracket = Bracket::new(&self.cfg.bracket, long, sig.price, size, stop_atr).map_err(|e| format!("{}: {}", sig.symbol, e))?;
+        // Candles up to the signal are already behind the entry
+        bracket.last_candle = candles.last().map_or(0, |c| c.timestamp);
+        let protective = |kind: &str, size: u64, price: f64, suffix: &str| OrderRequest {
+            stop_order_type: Some(kind.to_string()),
+            stop_price: Some(format_price(price, product.tick_size)),
+            reduce_only: true,
+            ..order(product, size, exit_side, client_order_id(sig, suffix))
+        };
+        Ok(OrderPlan {
+            entry: order(product, size, sig.direction, client_order_id(sig, "e")),
+            stop_loss: protective("stop_loss_order", size, bracket.stop_price, "sl"),
+            take_profits: bracket.targets.iter().enumerate()
+                .map(|(i, t)| protective("take_profit_order", t.size, t.price, &format!("tp{}", i + 1)))
+                .collect(),
+            bracket,
+        })
+    }
+
+    // Sends an order, or logs it in dry run; returns the exchange id when sent.
+    // `role` is "entry", "stop", "take_profit" or "exit".
+    async fn submit(&mut self, client: &DeltaClient, order: &OrderRequest, role: &str, now: u64) -> Result<Option<u64>, String> {
+        // Remembered before sending: a request that times out may still have been accepted
+        self.remember(&order.client_order_id);
+        let trigger = order.stop_price.as_deref().map_or(String::new(), |p| format!(" @ {}", p));
+        if self.cfg.dry_run {
+            info!("Dry run: {} {} {} contracts{} ({})", order.side, order.size, order.product_symbol, trigger, order.client_order_id);
+            return Ok(None);
+        }
+        self.state.orders.track(order, role, now);
+        match client.place_order(order).await {
+            Ok(id) => {
+                self.state.orders.acknowledged(&order.client_order_id, id, now);
+                info!("Order {} placed: {} {} {} contracts{} ({})", id, order.side, order.size, order.product_symbol, trigger, order.client_order_id);
+                Ok(Some(id))
+            }
+            Err(e) => {
+                self.state.orders.rejected(&order.client_order_id, &e, now);
+                Err(format!("{} {}: {}", order.product_symbol, order.client_order_id, e))
+            }
+        }
+    }
+
+    // Sets the exchange stop to the bracket's price and open size
+    async fn amend_stop(&self, client: &DeltaClient, symbol: &str) -> Result<(), String> {
+        let pos = match self.state.positions.get(symbol) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let tick = self.products.get(symbol).map_or(0.0, |p| p.tick_size);
+        let stop = format_price(pos.bracket.stop_price, tick);
+        if self.cfg.dry_run {
+            info!("Dry run: amend {} stop to {} contracts @ {}", symbol, pos.bracket.remaining, stop);
+            return Ok(());
+        }
+        let id = pos.stop_order_id.ok_or_else(|| format!("{}: no stop order to amend", symbol))?;
+        client.edit_order(pos.product_id, id, pos.bracket.remaining, &stop).await.map_err(|e| format!("{}: amend stop {}: {}", symbol, id, e))?;
+        info!("Stop {} amended: {} contracts @ {} ({})", id, pos.bracket.remaining, stop, symbol);
+        Ok(())
+    }
+
+    // Fits a position to its finished entry: `filled` contracts at `fill_price`
+    // (0 when unknown). The bracket is resized and re-priced from the fill, and
+    // the stop and the open take-profits are amended where they changed.
+    async fn rebracket(&mut self, client: &DeltaClient, symbol: &str, filled: u64, fill_price: f64) -> Result<(), String> {
+        let pos = match self.state.positions.get_mut(symbol) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let before: Vec<(u64, f64)> = pos.bracket.targets.iter().map(|t| (t.size, t.price)).collect();
+        let stop_before = pos.bracket.stop_price;
+        let resized = filled < pos.size;
+        if resized {
+            pos.size = filled;
+            pos.bracket.resize(filled);
+        }
+        if fill_price > 0.0 {
+            pos.entry_price = fill_price;
+            pos.bracket.reprice(fill_price);
+        }
+        let product_id = pos.product_id;
+        let changed: Vec<(u64, u64, f64)> = pos.take_profit_order_ids.iter().zip(&pos.bracket.targets).zip(before)
+            .filter(|((_, t), (size, price))| !t.hit && (t.size != *size || t.price != *price))
+            .filter_map(|((id, t), _)| Some(((*id)?, t.size, t.price)))
+            .collect();
+        if !resized && changed.is_empty() && pos.bracket.stop_price == stop_before {
+            return Ok(());
+        }
+        self.amend_stop(client, symbol).await?;
+        if self.cfg.dry_run {
+            return Ok(());
+        }
+        let tick = self.products.get(symbol).map_or(0.0, |p| p.tick_size);
+        for (id, size, price) in changed {
+            if size == 0 {
+                client.cancel_order(product_id, id).await.map_err(|e| format!("{}: cancel {}: {}", symbol, id, e))?;
+            } else {
+                client.edit_order(product_id, id, size, &format_price(price, tick)).await.map_err(|e| format!("{}: amend take-profit {}: {}", symbol, id, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Cancels the stop and any take-profits that have not filled
+    async fn cancel_protective(&self, client: &DeltaClient, pos: &LivePosition) -> Result<(), String> {
+        if self.cfg.dry_run {
+            return Ok(());
+        }
+        // Collected first: a borrowing iterator held across the awaits makes the future non-Send
+        let open_targets = pos.take_profit_order_ids.iter().zip(&pos.bracket.targets).filter(|(_, t)| !t.hit).map(|(id, _)| *id);
+        let ids: Vec<u64> = std::iter::once(pos.stop_order_id).chain(open_targets).flatten().collect();
+        for id in ids {
+            client.cancel_order(pos.product_id, id).await.map_err(|e| format!("{}: cancel {}: {}", pos.symbol, id, e))?;
+        }
+        Ok(())
+    }
+
+    // Advances a position's bracket over the candles closed since the last
+    // call, then amends its stop or retires the position to match. Live fills
+    // come from order updates; in dry run they are inferred from candle ranges.
+    pub async fn manage(&mut self, client: &DeltaClient, symbol: &str, closed: &HashMap<String, Vec<Candle>>) -> Result<(), String> {
+        let pos = match self.state.positions.get_mut(symbol) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let candles = match closed.get(&pos.timeframe) {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        let (remaining, last) = (pos.bracket.remaining, pos.bracket.last_candle);
+        let mut events = Vec::new();
+        for (i, candle) in candles.iter().enumerate().filter(|(_, c)| c.timestamp > last) {
+            let trail = brackets::trail_level(&self.cfg.bracket, pos.bracket.long, &candles[..=i]);
+            events.extend(pos.bracket.on_candle(&self.cfg.bracket, candle, trail, self.cfg.dry_run));
+        }
+        for event in &events {
+            match event {
+                BracketEvent::TargetHit(i) => info!("{}: target {} at {:.4} reached", symbol, i + 1, pos.bracket.targets[*i].price),
+                BracketEvent::StopMoved(p) => info!("{}: stop moved to {:.4}", symbol, p),
+                BracketEvent::Stopped => info!("{}: stopped out at {:.4}", symbol, pos.bracket.stop_price),
+            }
+        }
+        if pos.bracket.remaining == 0 {
+            let pos = self.state.positions.remove(symbol).expect("position present");
+            return self.cancel_protective(client, &pos).await;
+        }
+        let moved = events.iter().any(|e| matches!(e, BracketEvent::StopMoved(_)));
+        if !moved && pos.bracket.remaining == remaining {
+            return Ok(());
+        }
+        self.amend_stop(client, symbol).await
+    }
+
+    // Applies an exchange order report and updates the position it belongs to
+    pub async fn on_order_update(&mut self, client: &DeltaClient, update: &OrderUpdate, now: u64) -> Result<(), String> {
+        let t = match self.state.orders.apply(update, now) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let o = &t.order;
+        info!("Order {} ({} {}): {:?} -> {:?}, {}/{} filled", o.client_order_id, o.role, o.symbol, t.from, o.state, o.filled, o.size);
+        // Orders of an earlier position on the same symbol no longer matter
+        let pos = match self.state.positions.get_mut(&o.symbol) {
+            Some(p) if signal_key(&o.client_order_id) == signal_key(&p.client_order_id) => p,
+            _ => return Ok(()),
+        };
+        match (o.role.as_str(), o.state) {
+            ("stop", OrderState::Filled) => {
+                info!("{}: stopped out at {:.4}", o.symbol, o.avg_fill_price);
+                let pos = self.state.positions.remove(&o.symbol).expect("position present");
+                self.cancel_protective(client, &pos).await
+            }
+            ("take_profit", OrderState::Filled) => {
+                let i = match pos.take_profit_order_ids.iter().position(|id| *id == o.exchange_id) {
+                    Some(i) => i,
+                    None => return Ok(()),
+                };
+                pos.bracket.fill_target(i);
+                info!("{}: target {} filled at {:.4}, {} contracts open", o.symbol, i + 1, o.avg_fill_price, pos.bracket.remaining);
+                if pos.bracket.remaining == 0 {
+                    let pos = self.state.positions.remove(&o.symbol).expect("position present");
+                    return self.cancel_protective(client, &pos).await;
+                }
+                self.amend_stop(client, &o.symbol).await
+            }
+            ("entry", OrderState::Cancelled | OrderState::Rejected) if o.filled == 0 => {
+                info!("{}: entry {} was not filled", o.symbol, o.client_order_id);
+                let pos = self.state.positions.remove(&o.symbol).expect("position present");
+                self.cancel_protective(client, &pos).await
+            }
+            // The entry is done: the position is what actually filled, where it filled
+            ("entry", OrderState::Filled | OrderState::Cancelled | OrderState::Rejected) => {
+                if o.filled < pos.size {
+                    info!("{}: entry {} filled {} of {} contracts", o.symbol, o.client_order_id, o.filled, pos.size);
+                }
+                let (filled, fill_price) = (o.filled, o.avg_fill_price);
+                let symbol = o.symbol.clone();
+                self.rebracket(client, &symbol, filled, fill_price).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // REST fallback for missed stream events: every open order, then each
+    // tracked order the exchange no longer lists as open
+    pub async fn poll_orders(&mut self, client: &DeltaClient, now: u64) -> Result<(), String> {
+        if self.cfg.dry_run {
+            return Ok(());
+        }
+        let open: Vec<OrderUpdate> = client.fetch_open_orders().await?.iter().filter_map(OrderUpdate::from_json).collect();
+        for update in &open {
+            self.on_order_update(client, update, now).await?;
+        }
+        let missing: Vec<u64> = self.state.orders.live()
+            .filter_map(|o| o.exchange_id)
+            .filter(|id| !open.iter().any(|u| u.exchange_id == *id))
+            .collect();
+        for id in missing {
+            if let Some(update) = OrderUpdate::from_json(&client.fetch_order(id).await?) {
+                self.on_order_update(client, &update, now).await?;
+            }
+        }
+        self.state.orders.prune(self.cfg.max_tracked_orders);
+        Ok(())
+    }
+
+    // Compares local positions and orders with the exchange and fixes drift;
+    // returns a line per correction
+    pub async fn reconcile(&mut self, client: &DeltaClient, now: u64) -> Result<Vec<String>, String> {
+        let mut report = Vec::new();
+        if self.cfg.dry_run {
+            return Ok(report);
+        }
+        let exchange = client.fetch_positions().await?;
+        let symbols: Vec<String> = self.state.positions.keys().cloned().collect();
+        for symbol in symbols {
+            let pos = &self.state.positions[&symbol];
+            let open = pos.bracket.remaining as i64;
+            let expected = if pos.side == "long" { open } else { -open };
+            match exchange.get(&symbol).copied() {
+                Some(size) if size == expected => {}
+                Some(size) if size.signum() == expected.signum() => {
+                    report.push(format!("{}: open size {} on the exchange, {} locally; stop resized", symbol, size.abs(), open));
+                    if let Some(pos) = self.state.positions.get_mut(&symbol) {
+                        pos.bracket.remaining = size.unsigned_abs();
+                    }
+                    self.amend_stop(client, &symbol).await?;
+                }
+                _ => {
+                    report.push(format!("{}: position no longer open on the exchange; local state removed", symbol));
+                    let pos = self.state.positions.remove(&symbol).expect("position present");
+                    self.cancel_protective(client, &pos).await?;
+                }
+            }
+        }
+        for (symbol, size) in &exchange {
+            if !self.state.positions.contains_key(symbol) {
+                report.push(format!("{}: untracked position of {} contracts left alone", symbol, size));
+            }
+        }
+        for update in client.fetch_open_orders().await?.iter().filter_map(OrderUpdate::from_json) {
+            if !self.orphaned(&update) {
+                continue;
+            }
+            client.cancel_order(update.product_id, update.exchange_id).await.map_err(|e| format!("{}: cancel {}: {}", update.symbol, update.exchange_id, e))?;
+            report.push(format!("{}: cancelled orphaned {} {}", update.symbol, update.stop_order_type.as_deref().unwrap_or("reduce-only order"), update.exchange_id));
+            self.state.orders.apply(&OrderUpdate { state: "cancelled".to_string(), ..update }, now);
+        }
+        Ok(report)
+    }
+
+    // A reduce-only order we issued that the position on its symbol does not
+    // account for (or that has no position). Orders placed by anyone else,
+    // manually or by another system, are never touched.
+    fn orphaned(&self, update: &OrderUpdate) -> bool {
+        let id = match update.client_order_id.as_deref() {
+            Some(id) if update.reduce_only && self.state.sent.iter().any(|s| s == id) => id,
+            _ => return false,
+        };
+        !self.state.positions.get(&update.symbol).is_some_and(|p| {
+            signal_key(id) == signal_key(&p.client_order_id)
+                || std::iter::once(p.stop_order_id).chain(p.take_profit_order_ids.iter().copied()).any(|e| e == Some(update.exchange_id))
+        })
+    }
+
+    // Cancels a position's protective orders and closes it at market. The
+    // position stays tracked until both succeed, so a failure leaves it managed.
+    async fn close(&mut self, client: &DeltaClient, symbol: &str, client_order_id: String, now: u64) -> Result<(), String> {
+        let pos = match self.state.positions.get(symbol) {
+            Some(p) => p.clone(),
+            None => return Ok(()),
+        };
+        let product = self.products.get(symbol).cloned().ok_or_else(|| format!("{}: unknown product", symbol))?;
+        self.cancel_protective(client, &pos).await?;
+        if let Some(p) = self.state.positions.get_mut(symbol) {
+            p.stop_order_id = None;
+            p.take_profit_order_ids.iter_mut().for_each(|id| *id = None);
+        }
+        let side = if pos.side == "long" { "sell" } else { "buy" };
+        let exit = OrderRequest { reduce_only: true, ..order(&product, pos.bracket.remaining, side, client_order_id) };
+        self.submit(client, &exit, "exit", now).await?;
+        self.state.positions.remove(symbol);
+        Ok(())
+    }
+
+    // Acts on a signal: closes an opposite position, then enters with protective orders
+    pub async fn on_signal(&mut self, client: &DeltaClient, sig: &PipelineSignal, candles: &[Candle], now: u64) -> Result<(), String> {
+        if sig.provisional || sig.price <= 0.0 || !self.allowed(&sig.symbol) {
+            return Ok(());
+        }
+        let entry_id = client_order_id(sig, "e");
+        if self.state.sent.contains(&entry_id) {
+            info!("{}: signal already executed ({})", sig.symbol, entry_id);
+            return Ok(());
+        }
+        let product = self.products.get(&sig.symbol).cloned().ok_or_else(|| format!("{}: unknown product", sig.symbol))?;
+        let side = if sig.direction == "buy" { "long" } else { "short" };
+        if let Some(pos) = self.state.positions.get(&sig.symbol) {
+            if pos.side == side {
+                return Ok(());
+            }
+            self.close(client, &sig.symbol, client_order_id(sig, "x"), now).await?;
+        }
+        if side == "short" && !self.cfg.allow_short {
+            return Ok(());
+        }
+        let plan = self.plan(sig, &product, candles)?;
+        let notional = plan.entry.size as f64 * sig.price * product.contract_value;
+        self.risk.check(&sig.symbol, sig.direction, notional, &self.exposures(), now)
+            .map_err(|e| format!("{}: rejected by risk: {}", sig.symbol, e))?;
+        self.submit(client, &plan.entry, "entry", now).await?;
+        self.state.positions.insert(sig.symbol.clone(), LivePosition {
+            symbol: sig.symbol.clone(),
+            timeframe: sig.timeframe.clone(),
+            product_id: product.id,
+            side: side.to_string(),
+            size: plan.entry.size,
+            entry_price: sig.price,
+            entry_time: now,
+            client_order_id: entry_id,
+            stop_order_id: None,
+            take_profit_order_ids: Vec::new(),
+            bracket: plan.bracket,
+        });
+        // The entry is in; a failed protective order is reported but leaves the position tracked
+        let stop = self.submit(client, &plan.stop_loss, "stop", now).await;
+        let mut take_profits = Vec::new();
+        for tp in &plan.take_profits {
+            take_profits.push(self.submit(client, tp, "take_profit", now).await);
+        }
+        if let Some(pos) = self.state.positions.get_mut(&sig.symbol) {
+            pos.stop_order_id = stop.as_ref().ok().copied().flatten();
+            pos.take_profit_order_ids = take_profits.iter().map(|r| r.as_ref().ok().copied().flatten()).collect();
+        }
+        stop?;
+        take_profits.into_iter().try_for_each(|r| r.map(|_| ()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(candle_time: u64, price: f64) -> PipelineSignal {
+        PipelineSignal {
+            symbol: "BTCUSD".to_string(),
+            timeframe: "5m".to_string(),
+            direction: "buy",
+            score: 80.0,
+            breakdown: Vec::new(),
+            regime: None,
+            source: "scoring".to_string(),
+            provisional: false,
+            price,
+            candle_time,
+            sizing: None,
+        }
+    }
+
+    // Dry run, so nothing reaches the exchange
+    fn executor() -> Executor {
+        let cfg = ExecutionConfig { symbols: vec!["BTCUSD".to_string()], ..ExecutionConfig::default() };
+        let product = Product { id: 27, symbol: "BTCUSD".to_string(), tick_size: 0.5, contract_value: 0.01 };
+        let mut ex = Executor {
+            risk: RiskManager::new(cfg.risk.clone(), RiskState::default()),
+            cfg,
+            products: [("BTCUSD".to_string(), product)].into_iter().collect(),
+            state: ExecutionState::default(),
+            equity: 10_000.0,
+        };
+        ex.risk.update_equity(10_000.0, 0);
+        ex
+    }
+
+    fn client() -> DeltaClient {
+        DeltaClient::new(String::new(), String::new())
+    }
+
+    fn update(exchange_id: u64, client_id: &str, state: &str) -> OrderUpdate {
+        OrderUpdate {
+            exchange_id,
+            client_order_id: Some(client_id.to_string()),
+            product_id: 27,
+            symbol: "BTCUSD".to_string(),
+            size: 50,
+            unfilled: if state == "closed" { 0 } else { 50 },
+            state: state.to_string(),
+            avg_fill_price: 101.5,
+            reduce_only: true,
+            stop_order_type: Some("take_profit_order".to_string()),
+        }
+    }
+
+    #[test]
+    fn client_order_ids_follow_the_signal_candle() {
+        let id = client_order_id(&signal(1_700_000_100, 100.0), "e");
+        assert_eq!(id, "BTCUSD-5m-b-1700000100-e");
+        // A later cycle acting on the same candle derives the same id
+        assert_eq!(client_order_id(&signal(1_700_000_100, 101.0), "e"), id);
+        assert_ne!(client_order_id(&signal(1_700_000_400, 100.0), "e"), id);
+        let long = PipelineSignal { symbol: "VERYLONGSYMBOLUSD".to_string(), ..signal(1_700_000_100, 1.0) };
+        assert!(client_order_id(&long, "tp10").len() <= 32);
+        assert_eq!(signal_key(&client_order_id(&signal(1_700_000_100, 100.0), "tp2")), signal_key(&id));
+    }
+
+    #[tokio::test]
+    async fn a_signal_is_executed_once() {
+        let mut ex = executor();
+        let sig = signal(300, 100.0);
+        ex.on_signal(&client(), &sig, &[], 400).await.unwrap();
+        let pos = &ex.state.positions["BTCUSD"];
+        assert_eq!((pos.size, pos.client_order_id.as_str()), (100, "BTCUSD-5m-b-300-e"));
+        let sent = ex.state.sent.len();
+        // Closing the position locally must not let the same signal enter again
+        ex.state.positions.clear();
+        ex.on_signal(&client(), &sig, &[], 700).await.unwrap();
+        assert!(ex.state.positions.is_empty());
+        assert_eq!(ex.state.sent.len(), sent);
+    }
+
+    #[tokio::test]
+    async fn order_updates_only_touch_the_position_they_belong_to() {
+        let mut ex = executor();
+        ex.on_signal(&client(), &signal(300, 100.0), &[], 400).await.unwrap();
+        // Take-profits of the current position and of an earlier one on the same symbol
+        for (id, exchange_id) in [("BTCUSD-5m-b-300-tp1", 11), ("BTCUSD-5m-b-30-tp1", 21)] {
+            let request = OrderRequest { client_order_id: id.to_string(), ..order(&ex.products["BTCUSD"], 50, "sell", String::new()) };
+            ex.state.orders.track(&request, "take_profit", 400);
+            ex.state.orders.acknowledged(id, exchange_id, 400);
+        }
+        ex.state.positions.get_mut("BTCUSD").unwrap().take_profit_order_ids = vec![Some(11), Some(12)];
+        ex.on_order_update(&client(), &update(21, "BTCUSD-5m-b-30-tp1", "closed"), 500).await.unwrap();
+        assert_eq!(ex.state.positions["BTCUSD"].bracket.remaining, 100);
+        ex.on_order_update(&client(), &update(11, "BTCUSD-5m-b-300-tp1", "closed"), 500).await.unwrap();
+        let pos = &ex.state.positions["BTCUSD"];
+        assert!(pos.bracket.targets[0].hit);
+        assert_eq!(pos.bracket.remaining, 50);
+    }
+
+    #[tokio::test]
+    async fn entry_fill_reprices_the_bracket() {
+        let mut ex = executor();
+        ex.on_signal(&client(), &signal(300, 100.0), &[], 400).await.unwrap();
+        let request = OrderRequest { client_order_id: "BTCUSD-5m-b-300-e".to_string(), ..order(&ex.products["BTCUSD"], 100, "buy", String::new()) };
+        ex.state.orders.track(&request, "entry", 400);
+        let fill = OrderUpdate { size: 100, unfilled: 40, reduce_only: false, stop_order_type: None, avg_fill_price: 102.0, ..update(5, "BTCUSD-5m-b-300-e", "cancelled") };
+        ex.on_order_update(&client(), &fill, 410).await.unwrap();
+        let pos = &ex.state.positions["BTCUSD"];
+        assert_eq!((pos.size, pos.bracket.remaining, pos.entry_price), (60, 60, 102.0));
+        assert!((pos.bracket.stop_price - 100.47).abs() < 1e-9);
+        assert!((pos.bracket.targets[0].price - 103.53).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reconcile_only_cancels_our_unaccounted_reduce_only_orders() {
+        let mut ex = executor();
+        ex.state.sent.extend(["BTCUSD-5m-b-30-sl", "BTCUSD-5m-b-300-sl", "BTCUSD-5m-b-300-tp1"].map(str::to_string));
+        ex.state.positions.insert("BTCUSD".to_string(), LivePosition {
+            symbol: "BTCUSD".to_string(),
+            timeframe: "5m".to_string(),
+            product_id: 27,
+            side: "long".to_string(),
+            size: 100,
+            entry_price: 100.0,
+            entry_time: 400,
+            client_order_id: "BTCUSD-5m-b-300-e".to_string(),
+            stop_order_id: None,
+            take_profit_order_ids: vec![Some(11)],
+            bracket: Bracket::new(&BracketConfig::default(), true, 100.0, 100, None).unwrap(),
+        });
+        // A stop left over from an earlier position of ours
+        assert!(ex.orphaned(&update(20, "BTCUSD-5m-b-30-sl", "pending")));
+        // The current position's orders, known by exchange id or by client id
+        assert!(!ex.orphaned(&update(11, "BTCUSD-5m-b-300-tp1", "pending")));
+        assert!(!ex.orphaned(&update(10, "BTCUSD-5m-b-300-sl", "pending")));
+        // Orders placed outside the agent, with or without a position
+        assert!(!ex.orphaned(&update(30, "manual-stop", "pending")));
+        assert!(!ex.orphaned(&OrderUpdate { symbol: "ETHUSD".to_string(), client_order_id: None, ..update(31, "", "pending") }));
+        // Not reduce-only
+        assert!(!ex.orphaned(&OrderUpdate { reduce_only: false, ..update(20, "BTCUSD-5m-b-30-sl", "open") }));
+        ex.state.positions.clear();
+        assert!(ex.orphaned(&update(11, "BTCUSD-5m-b-300-tp1", "pending")));
+    }
+}
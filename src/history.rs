@@ -0,0 +1,245 @@
+// Closed-candle history per symbol and timeframe
+//
+// The tick buffer only spans the last few minutes, far less than the 1h
+// ranking lookbacks or RVOL's same time of day on previous days need. Closed
+// candles are therefore kept across cycles: candles built from ticks (with the
+// volume traded between ticks, so they match the exchange's candles) are
+// appended once their bucket has closed, if the buffer covers the whole
+// bucket, and closed buckets still missing are fetched from the exchange's
+// history API. On startup the history is seeded from storage when enabled, and
+// the API fills the rest. A candle already kept is never replaced.
+use std::collections::{BTreeMap, HashMap};
+
+use log::error;
+
+use crate::delta::{self, Candle, DeltaClient};
+use crate::pipeline::SymbolCandles;
+use crate::scoring::ScoringConfig;
+use crate::storage::Storage;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    // Closed candles kept per symbol and timeframe; raised where a lookback needs more
+    pub candles: usize,
+    // Fetch closed candles the tick buffer cannot provide from the exchange
+    pub fetch_from_api: bool,
+    // Trades kept per symbol in the tick buffer (and reloaded from storage)
+    pub ticks: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { candles: 300, fetch_from_api: true, ticks: 500 }
+    }
+}
+
+// Candles kept for a timeframe so every configured lookback is covered
+pub fn depth(cfg: &HistoryConfig, scoring: &ScoringConfig, tf: &str) -> usize {
+    let mut n = cfg.candles;
+    if tf == scoring.ranking.timeframe {
+        n = n.max(scoring.ranking.lookbacks.iter().max().map_or(0, |l| l + 1));
+    }
+    // Volume is analyzed on the primary and alert timeframes; RVOL looks back
+    // rvol_days whole days for candles at the same time of day
+    let volume_tf = scoring.timeframes.first().is_some_and(|t| t == tf) || scoring.volume.alert_timeframes.iter().any(|t| t == tf);
+    if let Some(secs) = delta::timeframe_seconds(tf).filter(|_| volume_tf) {
+        n = n.max((scoring.volume.rvol_days as u64 * 86_400 / secs) as usize + 1);
+    }
+    n
+}
+
+#[derive(Default)]
+pub struct CandleHistory {
+    depth: HashMap<String, usize>,
+    candles: HashMap<(String, String), Vec<Candle>>,
+}
+
+impl CandleHistory {
+    // `depth` is the number of candles kept per timeframe
+    pub fn new(depth: HashMap<String, usize>) -> Self {
+        Self { depth, candles: HashMap::new() }
+    }
+
+    pub fn timeframes(&self) -> impl Iterator<Item = (&String, usize)> {
+        self.depth.iter().map(|(tf, n)| (tf, *n))
+    }
+
+    // Open time of the newest kept candle
+    pub fn last_timestamp(&self, symbol: &str, tf: &str) -> Option<u64> {
+        self.candles.get(&(symbol.to_string(), tf.to_string()))?.last().map(|c| c.timestamp)
+    }
+
+    fn trim(&self, tf: &str, candles: &mut Vec<Candle>) {
+        let excess = candles.len().saturating_sub(self.depth.get(tf).copied().unwrap_or(0));
+        candles.drain(..excess);
+    }
+
+    // Adds candles from the history API or storage; timestamps already kept keep their candle
+    pub fn seed(&mut self, symbol: &str, tf: &str, candles: Vec<Candle>) {
+        let key = (symbol.to_string(), tf.to_string());
+        let mut merged: BTreeMap<u64, Candle> = candles.into_iter().map(|c| (c.timestamp, c)).collect();
+        merged.extend(self.candles.remove(&key).unwrap_or_default().into_iter().map(|c| (c.timestamp, c)));
+        let mut merged: Vec<Candle> = merged.into_values().collect();
+        self.trim(tf, &mut merged);
+        self.candles.insert(key, merged);
+    }
+
+    // Appends the newer closed candles built from ticks that start after
+    // `first_tick` (earlier buckets may miss trades that left the buffer, and
+    // the volume traded up to the first tick is unknown), then
+    // replaces each timeframe's closed candles with the kept history. Returns
+    // the appended candles per timeframe.
+    pub fn merge(&mut self, symbol: &str, candles: &mut SymbolCandles, first_tick: u64) -> Vec<(String, Vec<Candle>)> {
+        let mut added = Vec::new();
+        for (tf, built) in candles.closed.iter_mut() {
+            if !self.depth.contains_key(tf) {
+                continue;
+            }
+            let key = (symbol.to_string(), tf.clone());
+            let mut kept = self.candles.remove(&key).unwrap_or_default();
+            let last = kept.last().map(|c| c.timestamp);
+            let new: Vec<Candle> = built.iter()
+                .filter(|c| c.timestamp > first_tick && last.is_none_or(|l| c.timestamp > l))
+                .cloned()
+                .collect();
+            kept.extend(new.iter().cloned());
+            self.trim(tf, &mut kept);
+            *built = kept.clone();
+            self.candles.insert(key, kept);
+            if !new.is_empty() {
+                added.push((tf.clone(), new));
+            }
+        }
+        added
+    }
+}
+
+// Seeds `history` with the stored candles of every symbol and timeframe
+pub fn load_stored(history: &mut CandleHistory, db: &Storage, symbols: &[String]) {
+    let timeframes: Vec<(String, usize)> = history.timeframes().map(|(tf, n)| (tf.clone(), n)).collect();
+    for symbol in symbols {
+        for (tf, n) in &timeframes {
+            match db.load_candles(symbol, tf, u64::MAX, *n) {
+                Ok(candles) if !candles.is_empty() => history.seed(symbol, tf, candles),
+                Ok(_) => {}
+                Err(e) => error!("Failed to load stored {} {} candles: {}", symbol, tf, e),
+            }
+        }
+    }
+}
+
+// Fetches the closed candles `history` lacks for every symbol and timeframe:
+// the full depth when nothing is kept, else the buckets after the newest one.
+// Returns the fetched candles per (symbol, timeframe).
+pub async fn fetch_missing(history: &mut CandleHistory, client: &DeltaClient, symbols: &[String], now: u64) -> Vec<(String, String, Vec<Candle>)> {
+    let timeframes: Vec<(String, usize)> = history.timeframes().map(|(tf, n)| (tf.clone(), n)).collect();
+    let mut fetched = Vec::new();
+    for symbol in symbols {
+        for (tf, n) in &timeframes {
+            let secs = match delta::timeframe_seconds(tf) {
+                Some(s) => s,
+                None => continue,
+            };
+            // Open time of the newest closed bucket
+            let newest = (now / secs).saturating_sub(1) * secs;
+            let start = match history.last_timestamp(symbol, tf) {
+                Some(last) if last >= newest => continue,
+                // Never further back than the depth, however old the newest kept one is
+                Some(last) => (last + secs).max(now.saturating_sub(secs * *n as u64)),
+                None => now.saturating_sub(secs * *n as u64),
+            };
+            match client.fetch_history_candles(symbol, tf, start, now).await {
+                Ok(mut candles) => {
+                    candles.retain(|c| c.timestamp + secs <= now);
+                    if !candles.is_empty() {
+                        history.seed(symbol, tf, candles.clone());
+                        fetched.push((symbol.clone(), tf.clone(), candles));
+                    }
+                }
+                Err(e) => error!("Failed to fetch {} {} history: {}", symbol, tf, e),
+            }
+        }
+    }
+    fetched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(timestamp: u64, close: f64) -> Candle {
+        Candle { open: close, high: close, low: close, close, volume: 1.0, timestamp }
+    }
+
+    fn history(depth: usize) -> CandleHistory {
+        CandleHistory::new([("5m".to_string(), depth)].into_iter().collect())
+    }
+
+    fn built(candles: Vec<Candle>) -> SymbolCandles {
+        let mut s = SymbolCandles::default();
+        s.closed.insert("5m".to_string(), candles);
+        s
+    }
+
+    #[test]
+    fn appends_only_fully_covered_new_buckets() {
+        let mut h = history(10);
+        // The buffer starts mid-bucket at 310, so the 300 bucket is partial
+        let mut s = built(vec![candle(300, 1.0), candle(600, 2.0)]);
+        let added = h.merge("BTC", &mut s, 310);
+        assert_eq!(added.len(), 1);
+        assert_eq!(s.closed["5m"].iter().map(|c| c.timestamp).collect::<Vec<_>>(), vec![600]);
+        // Older and already kept buckets are ignored
+        let mut s = built(vec![candle(300, 9.0), candle(600, 9.0), candle(900, 3.0)]);
+        h.merge("BTC", &mut s, 0);
+        let closes: Vec<f64> = s.closed["5m"].iter().map(|c| c.close).collect();
+        assert_eq!(closes, vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn tick_candles_carry_traded_volume() {
+        // volume_24h snapshots: +5 and +3 in the 300 bucket, a drop as old
+        // volume leaves the window, then +4 in the 600 bucket
+        let ticks = [(1.0, 100.0, 300), (1.0, 105.0, 310), (1.0, 108.0, 320), (1.0, 90.0, 600), (1.0, 94.0, 610)];
+        let s = SymbolCandles::from_trades(&ticks, &["5m".to_string()], 900);
+        let volumes: Vec<f64> = s.closed["5m"].iter().map(|c| c.volume).collect();
+        assert_eq!(volumes, vec![8.0, 4.0]);
+    }
+
+    #[test]
+    fn seeded_history_wins_and_is_trimmed() {
+        let mut h = history(3);
+        let mut s = built(vec![candle(900, 5.0)]);
+        h.merge("BTC", &mut s, 0);
+        h.seed("BTC", "5m", (0..4).map(|i| candle(i * 300, i as f64)).collect());
+        let mut s = built(vec![candle(900, 7.0), candle(1200, 4.0)]);
+        h.merge("BTC", &mut s, 0);
+        let kept: Vec<(u64, f64)> = s.closed["5m"].iter().map(|c| (c.timestamp, c.close)).collect();
+        assert_eq!(kept, vec![(600, 2.0), (900, 5.0), (1200, 4.0)]);
+        assert_eq!(h.last_timestamp("BTC", "5m"), Some(1200));
+        assert_eq!(h.last_timestamp("ETH", "5m"), None);
+    }
+
+    #[test]
+    fn depth_covers_ranking_lookbacks() {
+        let scoring = ScoringConfig::default();
+        let cfg = HistoryConfig { candles: 50, ..HistoryConfig::default() };
+        assert_eq!(depth(&cfg, &scoring, "1h"), 169);
+        assert_eq!(depth(&cfg, &scoring, "4h"), 50);
+    }
+
+    #[test]
+    fn depth_covers_rvol_days() {
+        let scoring = ScoringConfig::default();
+        let cfg = HistoryConfig { candles: 50, ..HistoryConfig::default() };
+        // 5 days of 5m candles on the primary timeframe, plus the current one
+        assert_eq!(depth(&cfg, &scoring, "5m"), 5 * 288 + 1);
+        assert_eq!(depth(&cfg, &scoring, "15m"), 5 * 96 + 1);
+        // With that depth RVOL finds all five previous days
+        let candles: Vec<Candle> = (0..depth(&cfg, &scoring, "5m") as u64)
+            .map(|i| Candle { volume: if i % 288 == 0 { 2.0 } else { 1.0 }, ..candle(i * 300, 1.0) })
+            .collect();
+        assert_eq!(crate::volume::relative_volume(&candles, 5), Some(1.0));
+    }
+}
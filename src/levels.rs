@@ -0,0 +1,153 @@
+// Support/resistance zones from clustered swing points, and daily pivot points
+use std::collections::HashMap;
+
+use crate::delta::Candle;
+use crate::divergence;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct LevelConfig {
+    // Timeframe whose swings are clustered into zones
+    pub timeframe: String,
+    pub swing_lookback: usize,
+    // Swings within this % of a zone's centre join it
+    pub cluster_pct: f64,
+    // Swings needed for a zone to count
+    pub min_touches: usize,
+    // Distance (% of price) under which a level is considered close
+    pub near_pct: f64,
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self { timeframe: "1h".to_string(), swing_lookback: 5, cluster_pct: 0.5, min_touches: 2, near_pct: 1.0 }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Zone {
+    pub low: f64,
+    pub high: f64,
+    pub mid: f64,
+    pub touches: usize,
+    // "support" below the current price, "resistance" above it
+    pub kind: &'static str,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PivotSet {
+    pub pivot: f64,
+    pub resistances: Vec<f64>, // R1, R2, ...
+    pub supports: Vec<f64>,    // S1, S2, ...
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Pivots {
+    pub classic: PivotSet,
+    pub fibonacci: PivotSet,
+    pub camarilla: PivotSet,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SymbolLevels {
+    pub price: f64,
+    pub zones: Vec<Zone>,
+    pub pivots: Option<Pivots>,
+}
+
+impl SymbolLevels {
+    fn all_levels(&self) -> Vec<f64> {
+        let mut levels: Vec<f64> = self.zones.iter().map(|z| z.mid).collect();
+        if let Some(p) = &self.pivots {
+            levels.push(p.classic.pivot);
+            levels.extend(&p.classic.resistances);
+            levels.extend(&p.classic.supports);
+        }
+        levels
+    }
+
+    // Distance in % of price to the nearest level above the price
+    pub fn distance_to_resistance(&self) -> Option<f64> {
+        self.all_levels().into_iter()
+            .filter(|&l| l > self.price)
+            .map(|l| (l - self.price) / self.price * 100.0)
+            .fold(None, |acc: Option<f64>, d| Some(acc.map_or(d, |a| a.min(d))))
+    }
+
+    // Distance in % of price to the nearest level below the price
+    pub fn distance_to_support(&self) -> Option<f64> {
+        self.all_levels().into_iter()
+            .filter(|&l| l < self.price)
+            .map(|l| (self.price - l) / self.price * 100.0)
+            .fold(None, |acc: Option<f64>, d| Some(acc.map_or(d, |a| a.min(d))))
+    }
+}
+
+// Groups swing highs and lows whose prices are within cluster_pct of each other
+pub fn find_zones(candles: &[Candle], cfg: &LevelConfig) -> Vec<Zone> {
+    let price = match candles.last() {
+        Some(c) => c.close,
+        None => return Vec::new(),
+    };
+    let mut prices: Vec<f64> = divergence::find_swings(candles, cfg.swing_lookback).iter().map(|s| s.price).collect();
+    prices.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mut clusters: Vec<Vec<f64>> = Vec::new();
+    for p in prices {
+        match clusters.last_mut() {
+            Some(cluster) => {
+                let centre = cluster.iter().sum::<f64>() / cluster.len() as f64;
+                if (p - centre).abs() / centre * 100.0 <= cfg.cluster_pct {
+                    cluster.push(p);
+                } else {
+                    clusters.push(vec![p]);
+                }
+            }
+            None => clusters.push(vec![p]),
+        }
+    }
+    clusters.into_iter()
+        .filter(|c| c.len() >= cfg.min_touches)
+        .map(|c| {
+            let low = c.iter().cloned().fold(f64::MAX, f64::min);
+            let high = c.iter().cloned().fold(f64::MIN, f64::max);
+            let mid = c.iter().sum::<f64>() / c.len() as f64;
+            let kind = if mid < price { "support" } else { "resistance" };
+            Zone { low, high, mid, touches: c.len(), kind }
+        })
+        .collect()
+}
+
+// Classic, Fibonacci and Camarilla pivots from one (daily) candle
+pub fn pivot_points(prev: &Candle) -> Pivots {
+    let (h, l, c) = (prev.high, prev.low, prev.close);
+    let r = h - l;
+    let p = (h + l + c) / 3.0;
+    let classic = PivotSet {
+        pivot: p,
+        resistances: vec![2.0 * p - l, p + r, h + 2.0 * (p - l)],
+        supports: vec![2.0 * p - h, p - r, l - 2.0 * (h - p)],
+    };
+    let fibonacci = PivotSet {
+        pivot: p,
+        resistances: vec![p + 0.382 * r, p + 0.618 * r, p + r],
+        supports: vec![p - 0.382 * r, p - 0.618 * r, p - r],
+    };
+    let camarilla = PivotSet {
+        pivot: p,
+        resistances: [12.0, 6.0, 4.0, 2.0].iter().map(|d| c + r * 1.1 / d).collect(),
+        supports: [12.0, 6.0, 4.0, 2.0].iter().map(|d| c - r * 1.1 / d).collect(),
+    };
+    Pivots { classic, fibonacci, camarilla }
+}
+
+// Zones from cfg.timeframe and pivots from the last closed 1d candle (callers
+// pass closed candles, so that is the previous day)
+pub fn compute_levels(candles: &HashMap<String, Vec<Candle>>, cfg: &LevelConfig) -> Option<SymbolLevels> {
+    let tf_candles = candles.get(&cfg.timeframe)?;
+    let price = tf_candles.last()?.close;
+    let zones = find_zones(tf_candles, cfg);
+    let pivots = candles.get("1d")
+        .and_then(|d| d.last())
+        .map(pivot_points);
+    Some(SymbolLevels { price, zones, pivots })
+}
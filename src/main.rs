@@ -1,19 +1,59 @@
+mod backtest;
+mod brackets;
+mod config;
+mod debounce;
 mod delta;
+mod divergence;
 mod ema;
+mod history;
+mod execution;
+mod levels;
+mod metrics;
+mod montecarlo;
+mod optimize;
+mod orders;
+mod outcomes;
+mod paper;
+mod patterns;
+mod pipeline;
+mod profile;
+mod ranking;
+mod regime;
+mod report;
+mod risk;
+mod rules;
+mod scoring;
+mod script;
+mod sizing;
+mod storage;
 mod telegram;
+mod volume;
 mod web;
 
 use simple_logger::SimpleLogger;
 use log::{info, error};
 use tokio::time::{interval, Duration};
 
+// Recent (price, volume, timestamp) trades per symbol
+type TradeData = std::sync::Arc<tokio::sync::Mutex<storage::Ticks>>;
+// Trades waiting to be stored, as (symbol, price, volume, timestamp)
+type TickBuffer = std::sync::Arc<tokio::sync::Mutex<Vec<(String, f64, f64, u64)>>>;
+
 #[tokio::main]
 async fn main() {
     SimpleLogger::new().init().unwrap();
     info!("AI Agent started");
+    match std::env::args().nth(1).as_deref() {
+        Some("backtest") => return run_backtest().await,
+        Some("optimize") => return run_optimize().await,
+        _ => {}
+    }
     // TODO: Load API keys from environment or config
     let api_key = std::env::var("DELTA_API_KEY").expect("DELTA_API_KEY not set");
     let api_secret = std::env::var("DELTA_API_SECRET").expect("DELTA_API_SECRET not set");
+    // Orders go through their own client; the streaming ones move into their tasks
+    let order_client = delta::DeltaClient::new(api_key.clone(), api_secret.clone());
+    let order_stream_client = delta::DeltaClient::new(api_key.clone(), api_secret.clone());
     let delta_client = delta::DeltaClient::new(api_key, api_secret);
     let markets = match delta_client.fetch_perpetual_markets().await {
         Ok(m) => m,
@@ -23,6 +63,83 @@ async fn main() {
         }
     };
 
+    let strategy_config = match config::load_from_env() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to load strategy config: {}", e);
+            return;
+        }
+    };
+    let mut signal_pipeline = match build_pipeline(&strategy_config) {
+        Some(p) => p,
+        None => return,
+    };
+    let mut candle_timeframes = signal_pipeline.timeframes();
+    let exec_cfg = strategy_config.execution.clone();
+    let paper_cfg = strategy_config.paper.clone();
+    // Contract specs for order sizing and the paper book-depth model
+    let products = if exec_cfg.enabled || paper_cfg.enabled {
+        match order_client.fetch_products().await {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed to fetch products: {}", e);
+                return;
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let mut paper_engine = if paper_cfg.enabled {
+        let contract_values = products.iter().map(|p| (p.symbol.clone(), p.contract_value)).collect();
+        match paper::PaperEngine::load(paper_cfg, contract_values) {
+            Ok(engine) => Some(engine),
+            Err(e) => {
+                error!("Failed to load paper trading state: {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    let mut executor = if exec_cfg.enabled {
+        info!("Order execution enabled for {:?}{}", exec_cfg.symbols, if exec_cfg.dry_run { " (dry run)" } else { "" });
+        // The risk manager correlates closes of its own timeframe
+        if !candle_timeframes.contains(&exec_cfg.risk.correlation_timeframe) {
+            candle_timeframes.push(exec_cfg.risk.correlation_timeframe.clone());
+        }
+        match execution::Executor::load(exec_cfg, products) {
+            Ok(executor) => Some(executor),
+            Err(e) => {
+                error!("Failed to load execution state: {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    // Outcomes follow signals on closed candles of their own timeframe
+    if !candle_timeframes.contains(&strategy_config.outcomes.timeframe) {
+        candle_timeframes.push(strategy_config.outcomes.timeframe.clone());
+    }
+    let storage_cfg = strategy_config.storage.clone();
+    let mut storage = if storage_cfg.enabled {
+        match storage::Storage::open(&storage_cfg.path) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                error!("Failed to open storage: {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    if let (Some(db), Some(exec)) = (storage.as_ref(), executor.as_mut()) {
+        match db.open_orders() {
+            Ok(orders) => exec.restore_orders(orders),
+            Err(e) => error!("Failed to reload orders: {}", e),
+        }
+    }
+
     let telegram_token = std::env::var("TELEGRAM_BOT_TOKEN").expect("TELEGRAM_BOT_TOKEN not set");
     let telegram_chat_id = std::env::var("TELEGRAM_CHAT_ID").expect("TELEGRAM_CHAT_ID not set");
     let telegram_bot = telegram::TelegramBot::new(telegram_token, telegram_chat_id);
@@ -30,92 +147,453 @@ async fn main() {
     use std::collections::HashMap;
     use tokio::sync::Mutex;
     use std::sync::Arc;
-    // Store trades as (price, volume, timestamp)
-    let trade_data: Arc<Mutex<HashMap<String, Vec<(f64, f64, u64)>>>> = Arc::new(Mutex::new(HashMap::new()));
+    // Store trades as (price, volume, timestamp), at most history.ticks per symbol
+    let max_ticks = strategy_config.history.ticks.max(1);
+    let mut ticks = HashMap::new();
+    if let Some(db) = storage.as_ref().filter(|_| storage_cfg.store_ticks) {
+        match db.load_ticks(max_ticks) {
+            Ok(t) => ticks = t,
+            Err(e) => error!("Failed to reload ticks: {}", e),
+        }
+    }
+    let trade_data: TradeData = Arc::new(Mutex::new(ticks));
+    let tick_buffer: TickBuffer = Arc::new(Mutex::new(Vec::new()));
+    // Latest top of book per symbol
+    let quote_data: Arc<Mutex<HashMap<String, delta::Quote>>> = Arc::new(Mutex::new(HashMap::new()));
 
     let symbols = markets.clone();
     let trade_data_clone = trade_data.clone();
+    let quote_data_clone = quote_data.clone();
+    let tick_buffer_clone = tick_buffer.clone();
+    let store_ticks = storage.is_some() && storage_cfg.store_ticks;
     tokio::spawn(async move {
-        delta_client.stream_realtime_prices(symbols, move |symbol, price, volume| {
+        delta_client.stream_realtime_prices(symbols, move |symbol, price, volume, quote| {
+            if let Some(q) = quote {
+                futures::executor::block_on(quote_data_clone.lock()).insert(symbol.clone(), q);
+            }
             let mut data = futures::executor::block_on(trade_data_clone.lock());
             let ts = chrono::Utc::now().timestamp() as u64;
-            let entry = data.entry(symbol.clone()).or_insert_with(Vec::new);
+            if store_ticks {
+                futures::executor::block_on(tick_buffer_clone.lock()).push((symbol.clone(), price, volume, ts));
+            }
+            let entry = data.entry(symbol.clone()).or_default();
             entry.push((price, volume, ts));
-            if entry.len() > 500 { entry.remove(0); }
+            // Trimmed in chunks so each trade does not shift the whole buffer;
+            // it holds up to a quarter more than max_ticks in between
+            if entry.len() > max_ticks + max_ticks / 4 {
+                entry.drain(..entry.len() - max_ticks);
+            }
         }).await;
     });
 
     // Shared signal store for dashboard/API
-    let signal_store: web::SignalStore = Arc::new(Mutex::new(Vec::new()));
+    let mut last_signals = Vec::new();
+    if let Some(db) = storage.as_ref() {
+        match db.last_signals() {
+            Ok(signals) => last_signals = signals.into_iter().map(|s| web::SignalInfo {
+                coin: s.symbol,
+                timeframe: s.timeframe,
+                signal: s.direction,
+                strength: s.score.round() as i32,
+                breakdown: s.breakdown,
+                regime: s.regime,
+                provisional: s.provisional,
+                volume: s.volume,
+                timestamp: chrono::DateTime::from_timestamp(s.time as i64, 0).map_or(String::new(), |t| t.format("%H:%M:%S").to_string()),
+            }).collect(),
+            Err(e) => error!("Failed to reload signals: {}", e),
+        }
+    }
+    let signal_store: web::SignalStore = Arc::new(Mutex::new(last_signals));
     let trade_data_signal = trade_data.clone();
     let telegram_bot_signal = telegram_bot.clone();
     let signal_store_signal = signal_store.clone();
+    let level_store: web::LevelStore = Arc::new(Mutex::new(HashMap::new()));
+    let level_store_signal = level_store.clone();
+    let regime_store: web::RegimeStore = Arc::new(Mutex::new(HashMap::new()));
+    let regime_store_signal = regime_store.clone();
+    let ranking_store: web::RankingStore = Arc::new(Mutex::new(Vec::new()));
+    let ranking_store_signal = ranking_store.clone();
+    let profile_store: web::ProfileStore = Arc::new(Mutex::new(HashMap::new()));
+    let profile_store_signal = profile_store.clone();
+    let outcome_store: web::OutcomeStore = Arc::new(Mutex::new(outcomes::OutcomeSummary::default()));
+    let outcome_store_signal = outcome_store.clone();
+    let outcome_cfg = strategy_config.outcomes.clone();
+    let paper_store: web::PaperStore = Arc::new(Mutex::new(None));
+    let paper_store_signal = paper_store.clone();
+    let quote_data_signal = quote_data.clone();
+    let risk_store: web::RiskStore = Arc::new(Mutex::new(None));
+    let risk_store_signal = risk_store.clone();
+    let history_cfg = strategy_config.history.clone();
+    let depth = candle_timeframes.iter()
+        .map(|tf| (tf.clone(), history::depth(&history_cfg, &signal_pipeline.scoring.config, tf)))
+        .collect();
+    let mut candle_history = history::CandleHistory::new(depth);
+    let history_symbols = markets.clone();
+    // Order reports from the private channel, applied at the next cycle
+    let order_updates: Arc<Mutex<Vec<serde_json::Value>>> = Arc::new(Mutex::new(Vec::new()));
+    if executor.as_ref().is_some_and(|e| !e.cfg.dry_run) {
+        let order_updates = order_updates.clone();
+        tokio::spawn(async move {
+            order_stream_client.stream_order_updates(move |order| {
+                futures::executor::block_on(order_updates.lock()).push(order);
+            }).await;
+        });
+    }
     tokio::spawn(async move {
+        let mut volume_alerter = volume::VolumeAlerter::new();
+        let mut outcome_tracker = outcomes::OutcomeTracker::new();
         let mut interval = interval(Duration::from_secs(300)); // 5 minutes
+        let mut last_reconcile = 0;
+        let mut orders_saved = 0;
+        if let Some(db) = storage.as_ref() {
+            history::load_stored(&mut candle_history, db, &history_symbols);
+        }
+        if history_cfg.fetch_from_api {
+            info!("Fetching candle history for {} symbols", history_symbols.len());
+            let fetched = history::fetch_missing(&mut candle_history, &order_client, &history_symbols, chrono::Utc::now().timestamp() as u64).await;
+            if let Some(db) = storage.as_mut() {
+                if let Err(e) = db.save_candles(&fetched) {
+                    error!("Failed to store candles: {}", e);
+                }
+            }
+        }
         loop {
             interval.tick().await;
             let data = trade_data_signal.lock().await;
+            let now = chrono::Utc::now().timestamp() as u64;
             let mut new_signals = Vec::new();
-            for (symbol, trades) in data.iter() {
-                let mut tf_signals = Vec::new();
-                let mut tf_volumes = Vec::new();
-                for (tf_name, tf_minutes) in &[("5m", 5), ("15m", 15), ("1h", 60), ("4h", 240), ("1d", 1440)] {
-                    let candles = delta::aggregate_candles(trades, tf_minutes * 60);
-                    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
-                    let total_volume: f64 = candles.iter().map(|c| c.volume).sum();
-                    tf_volumes.push(total_volume);
-                    tf_signals.push(ema::detect_ema_signals(&closes));
-                }
-                let mut buy_count = 0;
-                let mut sell_count = 0;
-                let mut points = 0;
-                for sig in &tf_signals {
-                    match sig {
-                        Some("buy") => { buy_count += 1; points += 20; },
-                        Some("sell") => { sell_count += 1; points += 20; },
-                        _ => {}
+            // Latest trade price per symbol
+            let prices: HashMap<String, f64> = data.iter()
+                .filter_map(|(symbol, trades)| Some((symbol.clone(), trades.last()?.0)))
+                .collect();
+            if let Some(engine) = paper_engine.as_mut() {
+                engine.mark(&prices, now);
+            }
+            let mut universe: HashMap<String, pipeline::SymbolCandles> = data.iter()
+                .map(|(symbol, trades)| (symbol.clone(), pipeline::SymbolCandles::from_trades(trades, &candle_timeframes, now)))
+                .collect();
+            // Volume profiles from the raw trade vectors
+            let profile_cfg = signal_pipeline.scoring.config.profile.clone();
+            let profiles: HashMap<String, profile::VolumeProfile> = data.iter()
+                .filter_map(|(symbol, trades)| Some((symbol.clone(), profile::VolumeProfile::from_trades(trades, now, &profile_cfg)?)))
+                .collect();
+            // Candles added to the history this cycle, for storage
+            let mut new_candles = Vec::new();
+            for (symbol, candles) in universe.iter_mut() {
+                let first_tick = data.get(symbol).and_then(|t| t.first()).map_or(u64::MAX, |t| t.2);
+                let added = candle_history.merge(symbol, candles, first_tick);
+                new_candles.extend(added.into_iter().map(|(tf, c)| (symbol.clone(), tf, c)));
+            }
+            drop(data);
+            // Buckets the ticks could not fill come from the exchange
+            if history_cfg.fetch_from_api {
+                new_candles.extend(history::fetch_missing(&mut candle_history, &order_client, &history_symbols, now).await);
+                for (symbol, candles) in universe.iter_mut() {
+                    candle_history.merge(symbol, candles, u64::MAX);
+                }
+            }
+            // Follow earlier signals with the candles closed since the last cycle
+            for (symbol, candles) in universe.iter() {
+                if let Some(c) = candles.closed.get(&outcome_cfg.timeframe) {
+                    outcome_tracker.update(symbol, c, &outcome_cfg);
+                }
+            }
+            if let Some(db) = storage.as_mut() {
+                if let Err(e) = db.save_candles(&new_candles) {
+                    error!("Failed to store candles: {}", e);
+                }
+                if store_ticks {
+                    let ticks: Vec<(String, f64, f64, u64)> = tick_buffer.lock().await.drain(..).collect();
+                    if let Err(e) = db.save_ticks(&ticks) {
+                        error!("Failed to store ticks: {}", e);
+                    }
+                    if let Err(e) = db.prune_ticks(now.saturating_sub(storage_cfg.tick_retention_hours * 3600)) {
+                        error!("Failed to prune ticks: {}", e);
                     }
                 }
-                let max_volume = tf_volumes.iter().cloned().fold(0.0, f64::max);
-                let volume = tf_volumes.get(0).cloned().unwrap_or(0.0); // 5m volume
-                let volume_boost = if max_volume > 0.0 { ((volume / max_volume) * 20.0).round() as i32 } else { 0 };
-                let candles_5m = delta::aggregate_candles(trades, 5 * 60);
-                let closes_5m: Vec<f64> = candles_5m.iter().map(|c| c.close).collect();
-                let macd_signal = ema::detect_macd_crossover(&closes_5m);
-                let mut macd_boost = 0;
-                let direction = if buy_count >= 2 && points >= 40 {
-                    Some("buy")
-                } else if sell_count >= 2 && points >= 40 {
-                    Some("sell")
-                } else {
-                    None
-                };
-                if let (Some(macd), Some(dir)) = (macd_signal, direction.as_ref()) {
-                    if macd == *dir {
-                        macd_boost = 20;
+            }
+            if let Some(exec) = executor.as_mut() {
+                match order_client.fetch_wallet_balance(&exec.cfg.settlement_asset).await {
+                    // The daily loss counts open positions at their latest price
+                    Ok(balance) => match exec.update_equity(&order_client, balance + exec.unrealized_pnl(&prices), now).await {
+                        Ok(Some(risk::KillSwitchChange::Tripped(kill))) => {
+                            error!("Kill switch tripped: {}", kill.reason);
+                            let _ = telegram_bot_signal.send_notice(&format!("Kill switch tripped: {}. New entries are blocked until the next UTC day.", kill.reason)).await;
+                        }
+                        Ok(Some(risk::KillSwitchChange::Reset)) => {
+                            info!("Kill switch reset for the new day");
+                            let _ = telegram_bot_signal.send_notice("Kill switch reset for the new day").await;
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!("Kill switch failed: {}", e),
+                    },
+                    Err(e) => error!("Failed to fetch wallet balance: {}", e),
+                }
+                let updates: Vec<serde_json::Value> = order_updates.lock().await.drain(..).collect();
+                for update in updates.iter().filter_map(orders::OrderUpdate::from_json) {
+                    if let Err(e) = exec.on_order_update(&order_client, &update, now).await {
+                        error!("Order update failed: {}", e);
+                    }
+                }
+                if let Err(e) = exec.poll_orders(&order_client, now).await {
+                    error!("Order poll failed: {}", e);
+                }
+                if now.saturating_sub(last_reconcile) >= exec.cfg.reconcile_interval_secs {
+                    last_reconcile = now;
+                    match exec.reconcile(&order_client, now).await {
+                        Ok(report) if !report.is_empty() => {
+                            for line in &report {
+                                info!("Reconciliation: {}", line);
+                            }
+                            let _ = telegram_bot_signal.send_notice(&format!("Reconciliation:\n{}", report.join("\n"))).await;
+                        }
+                        Ok(_) => {}
+                        Err(e) => error!("Reconciliation failed: {}", e),
+                    }
+                }
+            }
+            *profile_store_signal.lock().await = profiles.clone();
+            signal_pipeline.set_profiles(profiles);
+            let ranking_cfg = signal_pipeline.scoring.config.ranking.clone();
+            let ranking_candles: HashMap<String, Vec<delta::Candle>> = universe.iter()
+                .filter_map(|(symbol, c)| Some((symbol.clone(), c.closed.get(&ranking_cfg.timeframe)?.clone())))
+                .collect();
+            let ranking = ranking::rank(&ranking_candles, &ranking_cfg);
+            signal_pipeline.set_rankings(&ranking);
+            *ranking_store_signal.lock().await = ranking;
+            if let Some(exec) = executor.as_mut() {
+                let tf = exec.cfg.risk.correlation_timeframe.clone();
+                for (symbol, candles) in universe.iter() {
+                    if let Some(c) = candles.closed.get(&tf) {
+                        exec.risk.set_closes(symbol, c.iter().map(|c| (c.timestamp, c.close)).collect());
+                    }
+                }
+            }
+            for (symbol, candles) in universe.iter() {
+                if let Some(lv) = levels::compute_levels(&candles.closed, &signal_pipeline.scoring.config.levels) {
+                    level_store_signal.lock().await.insert(symbol.clone(), lv);
+                }
+                regime_store_signal.lock().await.insert(symbol.clone(), signal_pipeline.scoring.regimes(&candles.closed));
+                for (tf, v) in volume_alerter.check(symbol, &candles.closed, &signal_pipeline.scoring.config.volume) {
+                    let rvol = v.rvol.map_or("-".to_string(), |r| format!("{:.1}x", r));
+                    info!("{} {}: unusual volume (score {:.1}, rvol {})", symbol, tf, v.score, rvol);
+                    let _ = telegram_bot_signal.send_alert(symbol, &tf, &format!("unusual activity: volume score {:.1}, RVOL {}", v.score, rvol)).await;
+                }
+                if let Some(exec) = executor.as_mut() {
+                    if let Err(e) = exec.manage(&order_client, symbol, &candles.closed).await {
+                        error!("Bracket update failed: {}", e);
                     }
                 }
-                let strength = points + volume_boost + macd_boost; // out of 140
-                if let Some(dir) = direction {
+                let primary_tf = signal_pipeline.primary_timeframe();
+                let volume: f64 = candles.closed.get(&primary_tf).map(|c| c.iter().map(|c| c.volume).sum()).unwrap_or(0.0);
+                for sig in signal_pipeline.evaluate(symbol, candles, now) {
+                    outcome_tracker.record(&sig, now, &outcome_cfg);
+                    let tf_candles = candles.closed.get(&sig.timeframe).map_or(&[][..], |c| &c[..]);
+                    if let Some(engine) = paper_engine.as_mut() {
+                        let quote = quote_data_signal.lock().await.get(symbol).cloned();
+                        if let Err(e) = engine.on_signal(&sig, tf_candles, quote.as_ref(), now) {
+                            info!("Paper trade skipped: {}", e);
+                        }
+                    }
+                    if let Some(exec) = executor.as_mut() {
+                        if let Err(e) = exec.on_signal(&order_client, &sig, tf_candles, now).await {
+                            error!("Order execution failed: {}", e);
+                        }
+                    }
+                    if let Some(db) = storage.as_ref() {
+                        let stored = storage::StoredSignal {
+                            time: now,
+                            symbol: symbol.clone(),
+                            timeframe: sig.timeframe.clone(),
+                            direction: sig.direction.to_string(),
+                            score: sig.score,
+                            source: sig.source.clone(),
+                            regime: sig.regime,
+                            provisional: sig.provisional,
+                            price: sig.price,
+                            volume,
+                            breakdown: sig.breakdown.clone(),
+                        };
+                        if let Err(e) = db.save_signal(&stored) {
+                            error!("Failed to store signal: {}", e);
+                        }
+                    }
                     let ts = chrono::Utc::now().format("%H:%M:%S").to_string();
-                    info!("{}: {} signal (strength: {} points, volume boost: {}, macd boost: {})", symbol, dir, strength, volume_boost, macd_boost);
-                    let _ = telegram_bot_signal.send_signal(symbol, "5m", &format!("{} (strength: {} points, volume boost: {}, macd boost: {})", dir, strength, volume_boost, macd_boost)).await;
+                    let strength = sig.score.round() as i32;
+                    let breakdown = scoring::format_breakdown(&sig.breakdown);
+                    let label = if sig.provisional { format!("provisional {}", sig.direction) } else { sig.direction.to_string() };
+                    info!("{}: {} signal from {} (score: {}/100, regime: {}; {})", symbol, label, sig.source, strength, sig.regime.map_or("-", |r| r.as_str()), breakdown);
+                    let _ = telegram_bot_signal.send_signal(symbol, &sig.timeframe, &format!("{} (score: {}/100, {}; {})", label, strength, sig.source, breakdown)).await;
                     // Push to signal store for dashboard/API
                     new_signals.push(web::SignalInfo {
                         coin: symbol.clone(),
-                        timeframe: "5m".to_string(),
-                        signal: dir.to_string(),
+                        timeframe: sig.timeframe.clone(),
+                        signal: sig.direction.to_string(),
                         strength,
+                        breakdown: sig.breakdown,
+                        regime: sig.regime,
+                        provisional: sig.provisional,
                         volume,
                         timestamp: ts,
                     });
                 }
             }
+            *outcome_store_signal.lock().await = outcome_tracker.summary(&outcome_cfg);
+            if let Some(engine) = paper_engine.as_ref() {
+                if let Err(e) = engine.save() {
+                    error!("Failed to save paper account: {}", e);
+                }
+                *paper_store_signal.lock().await = Some(engine.snapshot());
+            }
+            if let Some(exec) = executor.as_ref() {
+                if let Err(e) = exec.save() {
+                    error!("Failed to save execution state: {}", e);
+                }
+                *risk_store_signal.lock().await = Some(exec.risk_snapshot());
+                if let Some(db) = storage.as_mut() {
+                    match db.save_orders(exec.orders().iter(), orders_saved) {
+                        Ok(()) => orders_saved = now,
+                        Err(e) => error!("Failed to store orders: {}", e),
+                    }
+                }
+            }
             // Update shared signal store
             let mut store = signal_store_signal.lock().await;
             *store = new_signals;
         }
     });
     // Start web dashboard server with live signals
-    web::run_web_dashboard_with_signals(signal_store).await;
+    web::run_web_dashboard_with_signals(signal_store, level_store, regime_store, ranking_store, profile_store, outcome_store, paper_store, risk_store).await;
+}
+
+// Rules, scripts and scoring from the strategy config; errors are logged
+fn build_pipeline(strategy_config: &config::StrategyConfig) -> Option<pipeline::Pipeline> {
+    let signal_pipeline = match pipeline::Pipeline::from_config(strategy_config) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Invalid strategy: {}", e);
+            return None;
+        }
+    };
+    if !signal_pipeline.rules.is_empty() {
+        info!("Loaded {} strategy rules", signal_pipeline.rules.len());
+    }
+    if !signal_pipeline.scripts.is_empty() {
+        info!("Loaded {} strategy scripts", signal_pipeline.scripts.len());
+    }
+    Some(signal_pipeline)
+}
+
+// `ai_agent backtest`: replays historical candles through the pipeline and
+// writes the trade list and equity curve to the configured output file
+async fn run_backtest() {
+    let strategy_config = match config::load_from_env() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to load strategy config: {}", e);
+            return;
+        }
+    };
+    let signal_pipeline = match build_pipeline(&strategy_config) {
+        Some(p) => p,
+        None => return,
+    };
+    let cfg = strategy_config.backtest.clone();
+    // The history endpoint is public, so keys are optional here
+    let delta_client = delta::DeltaClient::new(
+        std::env::var("DELTA_API_KEY").unwrap_or_default(),
+        std::env::var("DELTA_API_SECRET").unwrap_or_default(),
+    );
+    let data = match backtest::load_data(&cfg, &delta_client).await {
+        Ok(d) => d,
+        Err(e) => {
+            error!("Failed to load backtest data: {}", e);
+            return;
+        }
+    };
+    for (symbol, candles) in &data {
+        info!("{}: {} {} candles", symbol, candles.len(), cfg.base_timeframe);
+    }
+    let result = match backtest::Backtester::new(cfg.clone(), strategy_config.history.clone(), signal_pipeline).run(&data) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Backtest failed: {}", e);
+            return;
+        }
+    };
+    let stats = metrics::compute(&result);
+    info!(
+        "Backtest: {} trades, return {:.2}%, Sharpe {:.2}, max drawdown {:.2}%, win rate {:.1}%",
+        stats.trade_stats.trades, stats.total_return_pct, stats.sharpe, stats.max_drawdown_pct, stats.trade_stats.win_rate,
+    );
+    let mc_cfg = &strategy_config.montecarlo;
+    let mc = (mc_cfg.simulations > 0).then(|| montecarlo::run(&result, mc_cfg));
+    if let Some(mc) = &mc {
+        for (method, s) in &mc.methods {
+            info!(
+                "Monte Carlo {}: final equity {:.2}..{:.2}, max drawdown {:.2}%..{:.2}%, risk of ruin {:.1}%",
+                method, s.final_equity.lower, s.final_equity.upper, s.max_drawdown_pct.lower, s.max_drawdown_pct.upper, s.risk_of_ruin_pct,
+            );
+        }
+        write_output(&mc_cfg.output, serde_json::to_string_pretty(mc));
+    }
+    write_output(&cfg.output, serde_json::to_string_pretty(&result));
+    write_output(&cfg.metrics_output, serde_json::to_string_pretty(&stats));
+    write_output(&cfg.report_output, Ok(report::render(&result, &stats, mc.as_ref())));
+}
+
+// `ai_agent optimize`: grid/random search over [optimize.params], with
+// walk-forward analysis when folds > 0
+async fn run_optimize() {
+    let (strategy_config, raw_config) = match config::load_from_env().and_then(|c| Ok((c, config::load_raw_from_env()?))) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to load strategy config: {}", e);
+            return;
+        }
+    };
+    let delta_client = delta::DeltaClient::new(
+        std::env::var("DELTA_API_KEY").unwrap_or_default(),
+        std::env::var("DELTA_API_SECRET").unwrap_or_default(),
+    );
+    let data = match backtest::load_data(&strategy_config.backtest, &delta_client).await {
+        Ok(d) => d,
+        Err(e) => {
+            error!("Failed to load backtest data: {}", e);
+            return;
+        }
+    };
+    let cfg = strategy_config.optimize.clone();
+    info!("Optimizing {} parameters ({} search)", cfg.params.len(), cfg.method);
+    // Backtests are CPU-bound; keep them off the async runtime
+    let report = match tokio::task::spawn_blocking(move || optimize::run(&raw_config, &cfg, &data)).await {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => {
+            error!("Optimization failed: {}", e);
+            return;
+        }
+        Err(e) => {
+            error!("Optimization task failed: {}", e);
+            return;
+        }
+    };
+    if let Some(best) = report.trials.first() {
+        info!("Best of {} trials: {:?} ({} = {:?})", report.trials.len(), best.params, report.objective, best.objective);
+    }
+    if let Some(wf) = &report.walk_forward {
+        for (name, s) in &wf.stability {
+            info!("{}: mean {:.4}, std {:.4}, mode share {:.0}% across {} folds", name, s.mean, s.std_dev, s.mode_share, wf.folds.len());
+        }
+        info!("Walk-forward efficiency: {:?}", wf.efficiency);
+    }
+    write_output(&strategy_config.optimize.output, serde_json::to_string_pretty(&report));
+}
+
+fn write_output(path: &str, contents: Result<String, serde_json::Error>) {
+    let result = contents.map_err(|e| e.to_string()).and_then(|c| std::fs::write(path, c).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => info!("Wrote {}", path),
+        Err(e) => error!("Failed to write {}: {}", path, e),
+    }
 }
@@ -0,0 +1,170 @@
+// Performance statistics for a backtest: returns, risk-adjusted ratios,
+// drawdowns and trade statistics, overall and per symbol/timeframe
+use std::collections::BTreeMap;
+
+use crate::backtest::{BacktestResult, EquityPoint, Trade};
+
+const YEAR_SECS: f64 = 365.0 * 86400.0;
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct TradeStats {
+    pub trades: usize,
+    pub net_pnl: f64,
+    // Percentages
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    // Mean net PnL per trade
+    pub expectancy: f64,
+    pub avg_win: f64,
+    pub avg_loss: f64,
+    // Seconds
+    pub avg_hold: f64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Metrics {
+    pub start: u64,
+    pub end: u64,
+    pub initial_equity: f64,
+    pub final_equity: f64,
+    pub total_return_pct: f64,
+    pub cagr_pct: f64,
+    pub sharpe: f64,
+    pub sortino: f64,
+    pub calmar: f64,
+    pub max_drawdown_pct: f64,
+    // Longest time underwater in seconds: from a peak to the recovery to it (or the end),
+    // whichever drawdown that was, not necessarily the deepest
+    pub max_drawdown_duration: u64,
+    // Share of time with at least one open position, in %
+    pub exposure_pct: f64,
+    pub trade_stats: TradeStats,
+    pub by_symbol: BTreeMap<String, TradeStats>,
+    pub by_timeframe: BTreeMap<String, TradeStats>,
+}
+
+pub fn trade_stats(trades: &[&Trade]) -> TradeStats {
+    if trades.is_empty() {
+        return TradeStats::default();
+    }
+    let n = trades.len() as f64;
+    let wins: Vec<f64> = trades.iter().map(|t| t.pnl).filter(|p| *p > 0.0).collect();
+    let losses: Vec<f64> = trades.iter().map(|t| t.pnl).filter(|p| *p <= 0.0).collect();
+    let gross_win: f64 = wins.iter().sum();
+    let gross_loss: f64 = -losses.iter().sum::<f64>();
+    let mean = |v: &[f64]| if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 };
+    let net_pnl = gross_win - gross_loss;
+    TradeStats {
+        trades: trades.len(),
+        net_pnl,
+        win_rate: wins.len() as f64 / n * 100.0,
+        profit_factor: if gross_loss > 0.0 { gross_win / gross_loss } else if gross_win > 0.0 { f64::INFINITY } else { 0.0 },
+        expectancy: net_pnl / n,
+        avg_win: mean(&wins),
+        avg_loss: mean(&losses),
+        avg_hold: trades.iter().map(|t| t.exit_time.saturating_sub(t.entry_time) as f64).sum::<f64>() / n,
+    }
+}
+
+// Drawdown in % below the running peak at each equity point
+pub fn drawdowns(equity: &[EquityPoint]) -> Vec<f64> {
+    let mut peak = f64::MIN;
+    equity.iter().map(|p| {
+        peak = peak.max(p.equity);
+        if peak > 0.0 { (peak - p.equity) / peak * 100.0 } else { 0.0 }
+    }).collect()
+}
+
+// Deepest drawdown in % and the longest stretch below a previous peak
+fn max_drawdown(equity: &[EquityPoint]) -> (f64, u64) {
+    let dd = drawdowns(equity);
+    let (mut max_dd, mut max_dur) = (0.0, 0);
+    let mut peak_ts = equity.first().map_or(0, |p| p.timestamp);
+    for (p, d) in equity.iter().zip(dd.iter()) {
+        if *d == 0.0 {
+            peak_ts = p.timestamp;
+        } else {
+            max_dur = max_dur.max(p.timestamp - peak_ts);
+        }
+        if *d > max_dd {
+            max_dd = *d;
+        }
+    }
+    (max_dd, max_dur)
+}
+
+pub fn compute(result: &BacktestResult) -> Metrics {
+    let equity = &result.equity;
+    let (start, end) = match (equity.first(), equity.last()) {
+        (Some(a), Some(b)) => (a.timestamp, b.timestamp),
+        _ => return Metrics { initial_equity: result.initial_equity, final_equity: result.final_equity, ..Metrics::default() },
+    };
+    let total_return = if result.initial_equity > 0.0 { result.final_equity / result.initial_equity - 1.0 } else { 0.0 };
+    let years = (end - start) as f64 / YEAR_SECS;
+    let cagr = if years > 0.0 && total_return > -1.0 { (1.0 + total_return).powf(1.0 / years) - 1.0 } else { 0.0 };
+
+    // Per-step returns, annualized by the equity curve's sampling interval
+    let returns: Vec<f64> = equity.windows(2)
+        .filter(|w| w[0].equity > 0.0)
+        .map(|w| w[1].equity / w[0].equity - 1.0)
+        .collect();
+    let step = if equity.len() > 1 { (end - start) as f64 / (equity.len() - 1) as f64 } else { 0.0 };
+    let periods_per_year = if step > 0.0 { YEAR_SECS / step } else { 0.0 };
+    let (sharpe, sortino) = if returns.len() > 1 {
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let sd = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        let downside = (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+        let annual = periods_per_year.sqrt();
+        (
+            if sd > 0.0 { mean / sd * annual } else { 0.0 },
+            if downside > 0.0 { mean / downside * annual } else { 0.0 },
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    let (max_dd, max_dd_duration) = max_drawdown(equity);
+    let exposed: u64 = equity.windows(2)
+        .filter(|w| w[0].open_positions > 0)
+        .map(|w| w[1].timestamp - w[0].timestamp)
+        .sum();
+
+    let all: Vec<&Trade> = result.trades.iter().collect();
+    let mut by_symbol: BTreeMap<String, Vec<&Trade>> = BTreeMap::new();
+    let mut by_timeframe: BTreeMap<String, Vec<&Trade>> = BTreeMap::new();
+    for t in &result.trades {
+        by_symbol.entry(t.symbol.clone()).or_default().push(t);
+        by_timeframe.entry(t.timeframe.clone()).or_default().push(t);
+    }
+
+    Metrics {
+        start,
+        end,
+        initial_equity: result.initial_equity,
+        final_equity: result.final_equity,
+        total_return_pct: total_return * 100.0,
+        cagr_pct: cagr * 100.0,
+        sharpe,
+        sortino,
+        calmar: if max_dd > 0.0 { cagr * 100.0 / max_dd } else { 0.0 },
+        max_drawdown_pct: max_dd,
+        max_drawdown_duration: max_dd_duration,
+        exposure_pct: if end > start { exposed as f64 / (end - start) as f64 * 100.0 } else { 0.0 },
+        trade_stats: trade_stats(&all),
+        by_symbol: by_symbol.iter().map(|(k, v)| (k.clone(), trade_stats(v))).collect(),
+        by_timeframe: by_timeframe.iter().map(|(k, v)| (k.clone(), trade_stats(v))).collect(),
+    }
+}
+
+// Duration as "3d 4h", "5h 12m" or "40m"
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs as u64;
+    let (d, h, m) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if d > 0 {
+        format!("{}d {}h", d, h)
+    } else if h > 0 {
+        format!("{}h {}m", h, m)
+    } else {
+        format!("{}m", m)
+    }
+}
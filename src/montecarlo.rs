@@ -0,0 +1,150 @@
+// Monte Carlo analysis of a backtest's trade list
+//
+// Three resampling methods, each run `simulations` times:
+//   shuffle   - same trades in random order (path risk; final equity is unchanged)
+//   bootstrap - as many trades drawn with replacement
+//   skip      - each trade independently missed with probability skip_pct
+// Each path starts at the initial equity and adds the trades' net PnL in turn.
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::backtest::BacktestResult;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct MonteCarloConfig {
+    // Paths per method (0 = disabled)
+    pub simulations: usize,
+    pub seed: u64,
+    pub skip_pct: f64,
+    // Two-sided confidence level of the reported intervals
+    pub confidence_pct: f64,
+    // A path is ruined once its drawdown reaches this (or equity hits zero)
+    pub ruin_drawdown_pct: f64,
+    pub output: String,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            simulations: 1000,
+            seed: 7,
+            skip_pct: 10.0,
+            confidence_pct: 90.0,
+            ruin_drawdown_pct: 50.0,
+            output: "backtest_montecarlo.json".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Interval {
+    pub lower: f64,
+    pub median: f64,
+    pub upper: f64,
+    pub mean: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MethodSummary {
+    pub final_equity: Interval,
+    pub max_drawdown_pct: Interval,
+    // Share of paths that were ruined, in %
+    pub risk_of_ruin_pct: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MonteCarloReport {
+    pub trades: usize,
+    pub simulations: usize,
+    pub confidence_pct: f64,
+    pub ruin_drawdown_pct: f64,
+    pub methods: BTreeMap<String, MethodSummary>,
+}
+
+struct Path {
+    final_equity: f64,
+    max_drawdown_pct: f64,
+    ruined: bool,
+}
+
+fn walk(initial: f64, pnls: &[f64], ruin_pct: f64) -> Path {
+    let (mut equity, mut peak, mut max_dd) = (initial, initial, 0.0f64);
+    let mut ruined = false;
+    for pnl in pnls {
+        equity += pnl;
+        peak = peak.max(equity);
+        let dd = if peak > 0.0 { (peak - equity) / peak * 100.0 } else { 100.0 };
+        max_dd = max_dd.max(dd);
+        if equity <= 0.0 || dd >= ruin_pct {
+            ruined = true;
+        }
+    }
+    Path { final_equity: equity, max_drawdown_pct: max_dd, ruined }
+}
+
+// Value at `pct` (0-100) of sorted values, linearly interpolated
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = pct / 100.0 * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+fn interval(mut values: Vec<f64>, confidence_pct: f64) -> Interval {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let tail = (100.0 - confidence_pct.clamp(0.0, 100.0)) / 2.0;
+    Interval {
+        lower: percentile(&values, tail),
+        median: percentile(&values, 50.0),
+        upper: percentile(&values, 100.0 - tail),
+        mean: if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 },
+    }
+}
+
+fn resample(method: &str, pnls: &[f64], skip_pct: f64, rng: &mut StdRng) -> Vec<f64> {
+    match method {
+        "shuffle" => {
+            let mut v = pnls.to_vec();
+            v.shuffle(rng);
+            v
+        }
+        "bootstrap" => (0..pnls.len()).map(|_| pnls[rng.gen_range(0..pnls.len())]).collect(),
+        _ => pnls.iter().copied().filter(|_| rng.gen_range(0.0..100.0) >= skip_pct).collect(),
+    }
+}
+
+pub fn run(result: &BacktestResult, cfg: &MonteCarloConfig) -> MonteCarloReport {
+    let pnls: Vec<f64> = result.trades.iter().map(|t| t.pnl).collect();
+    let mut methods = BTreeMap::new();
+    if !pnls.is_empty() {
+        for (m, method) in ["shuffle", "bootstrap", "skip"].iter().enumerate() {
+            // One seeded generator per path keeps results reproducible across thread counts
+            let paths: Vec<Path> = (0..cfg.simulations).into_par_iter()
+                .map(|i| {
+                    let mut rng = StdRng::seed_from_u64(cfg.seed ^ ((m as u64) << 32) ^ i as u64);
+                    walk(result.initial_equity, &resample(method, &pnls, cfg.skip_pct, &mut rng), cfg.ruin_drawdown_pct)
+                })
+                .collect();
+            let ruined = paths.iter().filter(|p| p.ruined).count();
+            methods.insert(method.to_string(), MethodSummary {
+                final_equity: interval(paths.iter().map(|p| p.final_equity).collect(), cfg.confidence_pct),
+                max_drawdown_pct: interval(paths.iter().map(|p| p.max_drawdown_pct).collect(), cfg.confidence_pct),
+                risk_of_ruin_pct: if paths.is_empty() { 0.0 } else { ruined as f64 / paths.len() as f64 * 100.0 },
+            });
+        }
+    }
+    MonteCarloReport {
+        trades: pnls.len(),
+        simulations: cfg.simulations,
+        confidence_pct: cfg.confidence_pct,
+        ruin_drawdown_pct: cfg.ruin_drawdown_pct,
+        methods,
+    }
+}
@@ -0,0 +1,284 @@
+// Parameter search over the strategy config with walk-forward validation
+//
+// Parameters are dotted paths into the strategy TOML (e.g.
+// "scoring.debounce.ema_fast" or "scoring.weights.macd"), so anything the
+// config can express can be tuned. Each candidate is backtested on its own
+// rayon worker. Walk-forward splits the data into consecutive folds, picks the
+// best candidate on each fold's in-sample window and scores it on the
+// out-of-sample window that follows.
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::backtest::Backtester;
+use crate::config;
+use crate::delta::Candle;
+use crate::metrics::{self, Metrics};
+use crate::pipeline::Pipeline;
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ParamSpec {
+    // Explicit candidates; otherwise min..=max in steps of `step`
+    pub values: Vec<f64>,
+    pub min: f64,
+    pub max: f64,
+    // 0 = continuous (random search only)
+    pub step: f64,
+}
+
+impl ParamSpec {
+    fn grid(&self) -> Vec<f64> {
+        if !self.values.is_empty() {
+            return self.values.clone();
+        }
+        if self.step <= 0.0 || self.max < self.min {
+            return vec![self.min];
+        }
+        let n = ((self.max - self.min) / self.step + 1e-9).floor() as usize;
+        (0..=n).map(|i| self.min + self.step * i as f64).collect()
+    }
+
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        if !self.values.is_empty() {
+            return self.values[rng.gen_range(0..self.values.len())];
+        }
+        if self.max <= self.min {
+            return self.min;
+        }
+        let v = rng.gen_range(self.min..=self.max);
+        if self.step > 0.0 { self.min + ((v - self.min) / self.step).round() * self.step } else { v }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct OptimizeConfig {
+    // "grid" or "random"
+    pub method: String,
+    // Candidates drawn by random search
+    pub samples: usize,
+    pub seed: u64,
+    // "sharpe", "sortino", "calmar", "total_return", "profit_factor" or "expectancy"
+    pub objective: String,
+    // Candidates with fewer trades get no objective
+    pub min_trades: usize,
+    pub params: BTreeMap<String, ParamSpec>,
+    // Walk-forward folds (0 = search the whole data set once)
+    pub folds: usize,
+    // Share of each fold used in-sample
+    pub in_sample_pct: f64,
+    // Worker threads (0 = all cores)
+    pub threads: usize,
+    pub output: String,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        Self {
+            method: "grid".to_string(),
+            samples: 100,
+            seed: 42,
+            objective: "sharpe".to_string(),
+            min_trades: 10,
+            params: BTreeMap::new(),
+            folds: 0,
+            in_sample_pct: 70.0,
+            threads: 0,
+            output: "optimize.json".to_string(),
+        }
+    }
+}
+
+pub type ParamSet = BTreeMap<String, f64>;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Trial {
+    pub params: ParamSet,
+    pub objective: Option<f64>,
+    pub metrics: Metrics,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Fold {
+    pub index: usize,
+    pub in_sample: (u64, u64),
+    pub out_of_sample: (u64, u64),
+    pub best: ParamSet,
+    pub in_sample_objective: Option<f64>,
+    pub out_of_sample_objective: Option<f64>,
+    pub out_of_sample_metrics: Metrics,
+}
+
+// Spread of one parameter's best value across folds
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ParamStability {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    // Standard deviation over |mean|; lower is more stable
+    pub cv: f64,
+    // Share of folds that picked the most common value, in %
+    pub mode_share: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct WalkForward {
+    pub folds: Vec<Fold>,
+    pub stability: BTreeMap<String, ParamStability>,
+    // Mean out-of-sample objective over mean in-sample objective
+    pub efficiency: Option<f64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct OptimizeReport {
+    pub method: String,
+    pub objective: String,
+    // Full-period trials, best first
+    pub trials: Vec<Trial>,
+    pub walk_forward: Option<WalkForward>,
+}
+
+pub fn candidates(cfg: &OptimizeConfig) -> Vec<ParamSet> {
+    if cfg.method == "random" {
+        let mut rng = StdRng::seed_from_u64(cfg.seed);
+        return (0..cfg.samples)
+            .map(|_| cfg.params.iter().map(|(k, spec)| (k.clone(), spec.sample(&mut rng))).collect())
+            .collect();
+    }
+    // Cartesian product of every parameter's grid
+    let mut sets = vec![ParamSet::new()];
+    for (name, spec) in &cfg.params {
+        sets = sets.into_iter()
+            .flat_map(|set| spec.grid().into_iter().map(move |v| {
+                let mut next = set.clone();
+                next.insert(name.clone(), v);
+                next
+            }))
+            .collect();
+    }
+    sets
+}
+
+pub fn objective(m: &Metrics, name: &str, min_trades: usize) -> Option<f64> {
+    if m.trade_stats.trades < min_trades {
+        return None;
+    }
+    let v = match name {
+        "sortino" => m.sortino,
+        "calmar" => m.calmar,
+        "total_return" => m.total_return_pct,
+        "profit_factor" => m.trade_stats.profit_factor,
+        "expectancy" => m.trade_stats.expectancy,
+        _ => m.sharpe,
+    };
+    if v.is_finite() { Some(v) } else { None }
+}
+
+// Candles in [from, to) for every symbol
+fn window(data: &HashMap<String, Vec<Candle>>, from: u64, to: u64) -> HashMap<String, Vec<Candle>> {
+    data.iter()
+        .map(|(s, c)| (s.clone(), c.iter().filter(|c| c.timestamp >= from && c.timestamp < to).cloned().collect()))
+        .collect()
+}
+
+// Backtests one parameter set; trading starts at `trade_from`, earlier candles are warmup
+fn run_trial(base: &toml::Value, params: &ParamSet, data: &HashMap<String, Vec<Candle>>, trade_from: u64) -> Result<Metrics, String> {
+    let mut value = base.clone();
+    for (path, v) in params {
+        config::set_path(&mut value, path, *v)?;
+    }
+    let cfg = config::from_value(value)?;
+    let pipeline = Pipeline::from_config(&cfg)?;
+    let result = Backtester::new(cfg.backtest.clone(), cfg.history.clone(), pipeline).trading_from(trade_from).run(data)?;
+    Ok(metrics::compute(&result))
+}
+
+fn search(base: &toml::Value, cfg: &OptimizeConfig, sets: &[ParamSet], data: &HashMap<String, Vec<Candle>>, trade_from: u64) -> Result<Vec<Trial>, String> {
+    let mut trials = sets.par_iter()
+        .map(|params| {
+            let metrics = run_trial(base, params, data, trade_from)?;
+            let objective = objective(&metrics, &cfg.objective, cfg.min_trades);
+            Ok(Trial { params: params.clone(), objective, metrics })
+        })
+        .collect::<Result<Vec<Trial>, String>>()?;
+    // Best first; candidates without an objective go last
+    trials.sort_by(|a, b| b.objective.unwrap_or(f64::MIN).partial_cmp(&a.objective.unwrap_or(f64::MIN)).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(trials)
+}
+
+fn stability(folds: &[Fold]) -> BTreeMap<String, ParamStability> {
+    let mut values: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for fold in folds {
+        for (k, v) in &fold.best {
+            values.entry(k.clone()).or_default().push(*v);
+        }
+    }
+    values.into_iter().map(|(k, vals)| {
+        let n = vals.len() as f64;
+        let mean = vals.iter().sum::<f64>() / n;
+        let std_dev = (vals.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let mode = vals.iter().map(|v| vals.iter().filter(|w| (*w - v).abs() < 1e-9).count()).max().unwrap_or(0);
+        (k, ParamStability {
+            mean,
+            std_dev,
+            min: vals.iter().cloned().fold(f64::MAX, f64::min),
+            max: vals.iter().cloned().fold(f64::MIN, f64::max),
+            cv: if mean != 0.0 { std_dev / mean.abs() } else { 0.0 },
+            mode_share: mode as f64 / n * 100.0,
+        })
+    }).collect()
+}
+
+fn walk_forward(base: &toml::Value, cfg: &OptimizeConfig, sets: &[ParamSet], data: &HashMap<String, Vec<Candle>>) -> Result<WalkForward, String> {
+    let start = data.values().filter_map(|c| c.first()).map(|c| c.timestamp).min().unwrap_or(0);
+    let end = data.values().filter_map(|c| c.last()).map(|c| c.timestamp).max().unwrap_or(0) + 1;
+    let span = (end - start) / cfg.folds as u64;
+    let mut folds = Vec::new();
+    for i in 0..cfg.folds {
+        let is_start = start + span * i as u64;
+        let fold_end = if i + 1 == cfg.folds { end } else { is_start + span };
+        let oos_start = is_start + ((fold_end - is_start) as f64 * cfg.in_sample_pct / 100.0) as u64;
+        let trials = search(base, cfg, sets, &window(data, is_start, oos_start), is_start)?;
+        // Folds where no candidate qualified say nothing about stability
+        let best = match trials.first() {
+            Some(t) if t.objective.is_some() => t,
+            _ => continue,
+        };
+        // The out-of-sample run warms up on the in-sample candles but only trades after them
+        let oos = run_trial(base, &best.params, &window(data, is_start, fold_end), oos_start)?;
+        folds.push(Fold {
+            index: i,
+            in_sample: (is_start, oos_start),
+            out_of_sample: (oos_start, fold_end),
+            best: best.params.clone(),
+            in_sample_objective: best.objective,
+            out_of_sample_objective: objective(&oos, &cfg.objective, 0),
+            out_of_sample_metrics: oos,
+        });
+    }
+    let mean = |vals: Vec<f64>| if vals.is_empty() { None } else { Some(vals.iter().sum::<f64>() / vals.len() as f64) };
+    let is_mean = mean(folds.iter().filter_map(|f| f.in_sample_objective).collect());
+    let oos_mean = mean(folds.iter().filter_map(|f| f.out_of_sample_objective).collect());
+    let efficiency = match (is_mean, oos_mean) {
+        (Some(is), Some(oos)) if is > 0.0 => Some(oos / is),
+        _ => None,
+    };
+    Ok(WalkForward { stability: stability(&folds), folds, efficiency })
+}
+
+pub fn run(base: &toml::Value, cfg: &OptimizeConfig, data: &HashMap<String, Vec<Candle>>) -> Result<OptimizeReport, String> {
+    let sets = candidates(cfg);
+    if sets.is_empty() || cfg.params.is_empty() {
+        return Err("no parameters to optimize ([optimize.params] is empty)".to_string());
+    }
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(cfg.threads).build().map_err(|e| e.to_string())?;
+    pool.install(|| {
+        let trials = search(base, cfg, &sets, data, 0)?;
+        let walk_forward = if cfg.folds > 0 { Some(walk_forward(base, cfg, &sets, data)?) } else { None };
+        Ok(OptimizeReport { method: cfg.method.clone(), objective: cfg.objective.clone(), trials, walk_forward })
+    })
+}
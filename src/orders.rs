@@ -0,0 +1,302 @@
+// Order lifecycle tracking
+//
+// Every order the executor sends is tracked by client order id through
+// new -> acknowledged -> partially_filled -> filled, or to cancelled or
+// rejected. Updates arrive from the private "orders" WebSocket channel and
+// from REST polling, possibly late, repeated or out of order: terminal states
+// are final and the filled size never decreases, so stale updates are ignored.
+use std::collections::BTreeMap;
+
+use crate::delta::OrderRequest;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderState {
+    New,
+    Acknowledged,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OrderState {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, OrderState::Filled | OrderState::Cancelled | OrderState::Rejected)
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct TrackedOrder {
+    pub client_order_id: String,
+    pub exchange_id: Option<u64>,
+    pub symbol: String,
+    pub product_id: u64,
+    pub side: String,
+    // "entry", "stop", "take_profit" or "exit"
+    pub role: String,
+    pub size: u64,
+    pub filled: u64,
+    pub avg_fill_price: f64,
+    pub state: OrderState,
+    pub reason: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+// One order as the exchange reports it
+#[derive(Clone, Debug)]
+pub struct OrderUpdate {
+    pub exchange_id: u64,
+    pub client_order_id: Option<String>,
+    pub product_id: u64,
+    pub symbol: String,
+    pub size: u64,
+    pub unfilled: u64,
+    // "open", "pending" (untriggered stop), "closed" or "cancelled"
+    pub state: String,
+    pub avg_fill_price: f64,
+    pub reduce_only: bool,
+    pub stop_order_type: Option<String>,
+}
+
+impl OrderUpdate {
+    // Parses an order object from the REST API or the "orders" channel
+    pub fn from_json(v: &serde_json::Value) -> Option<Self> {
+        let num = |k: &str| v.get(k).and_then(|x| x.as_f64().or_else(|| x.as_str()?.parse().ok()));
+        let text = |k: &str| v.get(k).and_then(|x| x.as_str()).filter(|s| !s.is_empty()).map(str::to_string);
+        let size = num("size")? as u64;
+        Some(Self {
+            exchange_id: v.get("id")?.as_u64()?,
+            client_order_id: text("client_order_id"),
+            product_id: v.get("product_id").and_then(|x| x.as_u64()).unwrap_or(0),
+            symbol: text("product_symbol").unwrap_or_default(),
+            size,
+            unfilled: num("unfilled_size").map_or(size, |u| u as u64),
+            state: text("state")?,
+            avg_fill_price: num("average_fill_price").unwrap_or(0.0),
+            reduce_only: v.get("reduce_only").and_then(|x| x.as_bool()).unwrap_or(false),
+            stop_order_type: text("stop_order_type"),
+        })
+    }
+
+    pub fn filled(&self) -> u64 {
+        self.size.saturating_sub(self.unfilled)
+    }
+
+    pub fn order_state(&self) -> OrderState {
+        match self.state.as_str() {
+            "closed" if self.unfilled == 0 => OrderState::Filled,
+            "closed" | "cancelled" => OrderState::Cancelled,
+            _ if self.filled() > 0 => OrderState::PartiallyFilled,
+            _ => OrderState::Acknowledged,
+        }
+    }
+}
+
+// A state change worth acting on
+#[derive(Clone, Debug)]
+pub struct Transition {
+    pub order: TrackedOrder,
+    pub from: OrderState,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct OrderTracker {
+    orders: BTreeMap<String, TrackedOrder>,
+}
+
+impl OrderTracker {
+    pub fn track(&mut self, order: &OrderRequest, role: &str, now: u64) {
+        self.orders.insert(order.client_order_id.clone(), TrackedOrder {
+            client_order_id: order.client_order_id.clone(),
+            exchange_id: None,
+            symbol: order.product_symbol.clone(),
+            product_id: order.product_id,
+            side: order.side.clone(),
+            role: role.to_string(),
+            size: order.size,
+            filled: 0,
+            avg_fill_price: 0.0,
+            state: OrderState::New,
+            reason: None,
+            created_at: now,
+            updated_at: now,
+        });
+    }
+
+    pub fn acknowledged(&mut self, client_order_id: &str, exchange_id: u64, now: u64) {
+        if let Some(o) = self.orders.get_mut(client_order_id) {
+            o.exchange_id = Some(exchange_id);
+            if o.state == OrderState::New {
+                o.state = OrderState::Acknowledged;
+                o.updated_at = now;
+            }
+        }
+    }
+
+    pub fn rejected(&mut self, client_order_id: &str, reason: &str, now: u64) {
+        if let Some(o) = self.orders.get_mut(client_order_id) {
+            if !o.state.is_terminal() {
+                o.state = OrderState::Rejected;
+                o.reason = Some(reason.to_string());
+                o.updated_at = now;
+            }
+        }
+    }
+
+    // Adds an order recovered from storage unless it is already tracked
+    pub fn restore(&mut self, order: TrackedOrder) {
+        self.orders.entry(order.client_order_id.clone()).or_insert(order);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders.values()
+    }
+
+    pub fn by_exchange_id(&self, id: u64) -> Option<&TrackedOrder> {
+        self.orders.values().find(|o| o.exchange_id == Some(id))
+    }
+
+    // Orders the exchange has accepted that have not reached a final state
+    pub fn live(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders.values().filter(|o| o.exchange_id.is_some() && !o.state.is_terminal())
+    }
+
+    // Applies an exchange report; None for unknown orders and stale or repeated updates
+    pub fn apply(&mut self, update: &OrderUpdate, now: u64) -> Option<Transition> {
+        let key = match &update.client_order_id {
+            Some(id) if self.orders.contains_key(id) => id.clone(),
+            _ => self.by_exchange_id(update.exchange_id)?.client_order_id.clone(),
+        };
+        let o = self.orders.get_mut(&key)?;
+        let state = update.order_state();
+        let filled = update.filled();
+        if o.state.is_terminal() || (state == o.state && filled <= o.filled) || filled < o.filled {
+            return None;
+        }
+        let from = o.state;
+        o.exchange_id = Some(update.exchange_id);
+        o.filled = filled;
+        if update.avg_fill_price > 0.0 {
+            o.avg_fill_price = update.avg_fill_price;
+        }
+        o.state = state;
+        o.updated_at = now;
+        Some(Transition { order: o.clone(), from })
+    }
+
+    // Drops the oldest finished orders beyond `max`
+    pub fn prune(&mut self, max: usize) {
+        if self.orders.len() <= max {
+            return;
+        }
+        let mut finished: Vec<(u64, String)> = self.orders.values()
+            .filter(|o| o.state.is_terminal())
+            .map(|o| (o.updated_at, o.client_order_id.clone()))
+            .collect();
+        finished.sort();
+        let excess = self.orders.len() - max;
+        for (_, id) in finished.into_iter().take(excess) {
+            self.orders.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: &str, size: u64) -> OrderRequest {
+        OrderRequest {
+            product_id: 27,
+            product_symbol: "BTCUSD".to_string(),
+            size,
+            side: "buy".to_string(),
+            order_type: "market_order".to_string(),
+            limit_price: None,
+            stop_order_type: None,
+            stop_price: None,
+            reduce_only: false,
+            client_order_id: id.to_string(),
+        }
+    }
+
+    fn update(exchange_id: u64, client_id: Option<&str>, size: u64, unfilled: u64, state: &str) -> OrderUpdate {
+        OrderUpdate {
+            exchange_id,
+            client_order_id: client_id.map(str::to_string),
+            product_id: 27,
+            symbol: "BTCUSD".to_string(),
+            size,
+            unfilled,
+            state: state.to_string(),
+            avg_fill_price: 100.0,
+            reduce_only: false,
+            stop_order_type: None,
+        }
+    }
+
+    fn tracker() -> OrderTracker {
+        let mut t = OrderTracker::default();
+        t.track(&request("a", 10), "entry", 1);
+        t.acknowledged("a", 7, 2);
+        t
+    }
+
+    #[test]
+    fn applies_fills_through_to_filled() {
+        let mut t = tracker();
+        let tr = t.apply(&update(7, Some("a"), 10, 6, "open"), 3).unwrap();
+        assert_eq!((tr.from, tr.order.state, tr.order.filled), (OrderState::Acknowledged, OrderState::PartiallyFilled, 4));
+        // Matched by exchange id when the report has no client id
+        let tr = t.apply(&update(7, None, 10, 0, "closed"), 4).unwrap();
+        assert_eq!((tr.order.state, tr.order.filled), (OrderState::Filled, 10));
+        assert_eq!(t.live().count(), 0);
+        assert!(t.apply(&update(8, None, 10, 0, "closed"), 5).is_none());
+    }
+
+    #[test]
+    fn ignores_stale_and_repeated_updates() {
+        let mut t = tracker();
+        t.apply(&update(7, Some("a"), 10, 4, "open"), 3).unwrap();
+        // Same fill again, then an older one with less filled
+        assert!(t.apply(&update(7, Some("a"), 10, 4, "open"), 4).is_none());
+        assert!(t.apply(&update(7, Some("a"), 10, 8, "open"), 5).is_none());
+        // A cancel after part of the order filled keeps the fill
+        let tr = t.apply(&update(7, Some("a"), 10, 4, "cancelled"), 6).unwrap();
+        assert_eq!((tr.order.state, tr.order.filled), (OrderState::Cancelled, 6));
+        // Terminal states are final
+        assert!(t.apply(&update(7, Some("a"), 10, 0, "closed"), 7).is_none());
+        assert_eq!(t.iter().next().unwrap().updated_at, 6);
+    }
+
+    #[test]
+    fn rejected_orders_stay_rejected() {
+        let mut t = OrderTracker::default();
+        t.track(&request("b", 5), "stop", 1);
+        t.rejected("b", "insufficient margin", 2);
+        t.acknowledged("b", 9, 3);
+        let o = t.iter().next().unwrap();
+        assert_eq!((o.state, o.reason.as_deref()), (OrderState::Rejected, Some("insufficient margin")));
+    }
+
+    #[test]
+    fn prune_drops_oldest_finished_orders_only() {
+        let mut t = OrderTracker::default();
+        for (i, id) in ["a", "b", "c", "d"].iter().enumerate() {
+            t.track(&request(id, 1), "entry", i as u64);
+            t.acknowledged(id, i as u64 + 1, i as u64);
+        }
+        // "b" and "c" finish, "c" first; "a" and "d" stay live
+        t.apply(&update(3, Some("c"), 1, 0, "closed"), 10).unwrap();
+        t.apply(&update(2, Some("b"), 1, 0, "closed"), 11).unwrap();
+        t.prune(3);
+        let ids: Vec<&str> = t.iter().map(|o| o.client_order_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "d"]);
+        // Live orders are never dropped, even over the limit
+        t.prune(1);
+        let ids: Vec<&str> = t.iter().map(|o| o.client_order_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "d"]);
+    }
+}
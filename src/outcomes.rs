@@ -0,0 +1,216 @@
+// Live outcome tracking: every emitted signal is followed forward in price
+// and scored on forward returns, excursions and whether its target or stop hit
+// first, so hit rates can be compared across symbols, timeframes, strength
+// buckets and the components that contributed to the score.
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::delta::{self, Candle};
+use crate::pipeline::PipelineSignal;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct OutcomeConfig {
+    // Closed candles of this timeframe drive returns, excursions and hits
+    pub timeframe: String,
+    // Forward return horizons
+    pub horizons: Vec<String>,
+    // Favorable/adverse moves from entry, in %
+    pub target_pct: f64,
+    pub stop_pct: f64,
+    // Width of the strength buckets (e.g. 20 -> "40-60")
+    pub bucket_size: f64,
+    // Signals kept (oldest are dropped)
+    pub max_signals: usize,
+}
+
+impl Default for OutcomeConfig {
+    fn default() -> Self {
+        Self {
+            timeframe: "5m".to_string(),
+            horizons: vec!["1h".to_string(), "4h".to_string(), "24h".to_string()],
+            target_pct: 2.0,
+            stop_pct: 1.0,
+            bucket_size: 20.0,
+            max_signals: 5000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TrackedSignal {
+    pub symbol: String,
+    pub timeframe: String,
+    pub direction: &'static str,
+    pub score: f64,
+    pub source: String,
+    // Components that added points to the score
+    pub components: Vec<String>,
+    pub entry_price: f64,
+    pub entry_time: u64,
+    // Signed in the signal's direction, in %; None until the horizon passes
+    pub forward_returns: BTreeMap<String, Option<f64>>,
+    // Maximum favorable / adverse excursion so far, in %
+    pub mfe_pct: f64,
+    pub mae_pct: f64,
+    // "target", "stop" or "neither" once decided
+    pub first_hit: Option<&'static str>,
+    pub last_update: u64,
+    pub complete: bool,
+}
+
+impl TrackedSignal {
+    fn signed_return(&self, price: f64) -> f64 {
+        let r = (price / self.entry_price - 1.0) * 100.0;
+        if self.direction == "buy" { r } else { -r }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct HitStats {
+    pub signals: usize,
+    // Per horizon: share of resolved signals with a positive return, in %
+    pub hit_rate: BTreeMap<String, f64>,
+    pub avg_return: BTreeMap<String, f64>,
+    // Share of decided signals whose target hit before the stop, in %
+    pub target_first_rate: f64,
+    pub avg_mfe_pct: f64,
+    pub avg_mae_pct: f64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct OutcomeSummary {
+    pub overall: HitStats,
+    pub by_symbol: BTreeMap<String, HitStats>,
+    pub by_timeframe: BTreeMap<String, HitStats>,
+    pub by_strength: BTreeMap<String, HitStats>,
+    pub by_component: BTreeMap<String, HitStats>,
+}
+
+#[derive(Default)]
+pub struct OutcomeTracker {
+    signals: VecDeque<TrackedSignal>,
+}
+
+impl OutcomeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts following a signal; provisional ones may still vanish and are skipped
+    pub fn record(&mut self, sig: &PipelineSignal, now: u64, cfg: &OutcomeConfig) {
+        if sig.provisional || sig.price <= 0.0 {
+            return;
+        }
+        self.signals.push_back(TrackedSignal {
+            symbol: sig.symbol.clone(),
+            timeframe: sig.timeframe.clone(),
+            direction: sig.direction,
+            score: sig.score,
+            source: sig.source.clone(),
+            components: sig.breakdown.iter().filter(|c| c.points > 0.0).map(|c| c.name.clone()).collect(),
+            entry_price: sig.price,
+            entry_time: now,
+            forward_returns: cfg.horizons.iter().map(|h| (h.clone(), None)).collect(),
+            mfe_pct: 0.0,
+            mae_pct: 0.0,
+            first_hit: None,
+            last_update: now,
+            complete: false,
+        });
+        while self.signals.len() > cfg.max_signals {
+            self.signals.pop_front();
+        }
+    }
+
+    // Feeds closed candles of `cfg.timeframe` for one symbol to its open signals.
+    // Only candles opening after the signal count; each candle's high and low
+    // give the excursions, and a candle reaching both stop and target counts
+    // as a stop since the order inside it is unknown.
+    pub fn update(&mut self, symbol: &str, candles: &[Candle], cfg: &OutcomeConfig) {
+        let secs = match delta::timeframe_seconds(&cfg.timeframe) {
+            Some(s) => s,
+            None => return,
+        };
+        let longest = cfg.horizons.iter().filter_map(|h| delta::timeframe_seconds(h)).max().unwrap_or(0);
+        for sig in self.signals.iter_mut().filter(|s| !s.complete && s.symbol == symbol) {
+            let since = sig.last_update;
+            for c in candles.iter().filter(|c| c.timestamp >= since) {
+                let (a, b) = (sig.signed_return(c.high), sig.signed_return(c.low));
+                let (favorable, adverse) = (a.max(b), a.min(b));
+                let close = c.timestamp + secs;
+                // Excursions are measured up to the longest horizon only
+                if c.timestamp < sig.entry_time + longest {
+                    sig.mfe_pct = sig.mfe_pct.max(favorable);
+                    sig.mae_pct = sig.mae_pct.min(adverse);
+                    if sig.first_hit.is_none() {
+                        if adverse <= -cfg.stop_pct {
+                            sig.first_hit = Some("stop");
+                        } else if favorable >= cfg.target_pct {
+                            sig.first_hit = Some("target");
+                        }
+                    }
+                }
+                // Close of the first candle ending at or after each horizon
+                let r = sig.signed_return(c.close);
+                for (h, slot) in sig.forward_returns.iter_mut() {
+                    let h = delta::timeframe_seconds(h).unwrap_or(0);
+                    if slot.is_none() && close >= sig.entry_time + h {
+                        *slot = Some(r);
+                    }
+                }
+                sig.last_update = close;
+            }
+            if sig.forward_returns.values().all(|r| r.is_some()) {
+                sig.first_hit.get_or_insert("neither");
+                sig.complete = true;
+            }
+        }
+    }
+
+    pub fn summary(&self, cfg: &OutcomeConfig) -> OutcomeSummary {
+        let all: Vec<&TrackedSignal> = self.signals.iter().collect();
+        let mut by_symbol: BTreeMap<String, Vec<&TrackedSignal>> = BTreeMap::new();
+        let mut by_timeframe: BTreeMap<String, Vec<&TrackedSignal>> = BTreeMap::new();
+        let mut by_strength: BTreeMap<String, Vec<&TrackedSignal>> = BTreeMap::new();
+        let mut by_component: BTreeMap<String, Vec<&TrackedSignal>> = BTreeMap::new();
+        let size = if cfg.bucket_size > 0.0 { cfg.bucket_size } else { 100.0 };
+        for s in &all {
+            by_symbol.entry(s.symbol.clone()).or_default().push(s);
+            by_timeframe.entry(s.timeframe.clone()).or_default().push(s);
+            let lo = ((s.score / size).floor() * size).min(100.0 - size).max(0.0);
+            by_strength.entry(format!("{}-{}", lo as u32, (lo + size) as u32)).or_default().push(s);
+            for c in &s.components {
+                by_component.entry(c.clone()).or_default().push(s);
+            }
+        }
+        let group = |m: BTreeMap<String, Vec<&TrackedSignal>>| m.into_iter().map(|(k, v)| (k, hit_stats(&v, cfg))).collect();
+        OutcomeSummary {
+            overall: hit_stats(&all, cfg),
+            by_symbol: group(by_symbol),
+            by_timeframe: group(by_timeframe),
+            by_strength: group(by_strength),
+            by_component: group(by_component),
+        }
+    }
+}
+
+fn hit_stats(signals: &[&TrackedSignal], cfg: &OutcomeConfig) -> HitStats {
+    let mean = |v: &[f64]| if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 };
+    let mut stats = HitStats { signals: signals.len(), ..HitStats::default() };
+    for h in &cfg.horizons {
+        let resolved: Vec<f64> = signals.iter().filter_map(|s| s.forward_returns.get(h).copied().flatten()).collect();
+        if resolved.is_empty() {
+            continue;
+        }
+        let hits = resolved.iter().filter(|r| **r > 0.0).count();
+        stats.hit_rate.insert(h.clone(), hits as f64 / resolved.len() as f64 * 100.0);
+        stats.avg_return.insert(h.clone(), mean(&resolved));
+    }
+    let decided: Vec<&str> = signals.iter().filter_map(|s| s.first_hit).filter(|h| *h != "neither").collect();
+    if !decided.is_empty() {
+        stats.target_first_rate = decided.iter().filter(|h| **h == "target").count() as f64 / decided.len() as f64 * 100.0;
+    }
+    stats.avg_mfe_pct = mean(&signals.iter().map(|s| s.mfe_pct).collect::<Vec<_>>());
+    stats.avg_mae_pct = mean(&signals.iter().map(|s| s.mae_pct).collect::<Vec<_>>());
+    stats
+}
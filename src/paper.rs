@@ -0,0 +1,299 @@
+// Paper trading: a simulated margin account that follows live signals
+//
+// Each signal closes an opposite position and opens a new one at a modeled
+// fill price. Fills cross the spread ("spread"), additionally walk past the
+// top of book in proportion to size ("depth"), or use a fixed slippage when no
+// quote is available. The account is saved as JSON after every cycle and
+// reloaded on start; a state file that cannot be read stops the start rather
+// than silently opening a fresh account.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::delta::{Candle, Quote};
+use crate::pipeline::PipelineSignal;
+use crate::sizing::{self, SizingConfig, SizingInput};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct PaperConfig {
+    pub enabled: bool,
+    pub initial_balance: f64,
+    pub leverage: f64,
+    // Per side, as % of notional
+    pub fee_pct: f64,
+    // "spread", "depth" or "fixed"
+    pub slippage_model: String,
+    // Used by "fixed" and when there is no quote, in %
+    pub slippage_pct: f64,
+    // Extra slippage per multiple of the top-of-book size ("depth"), in %
+    pub depth_impact_pct: f64,
+    // Charged to longs (paid to shorts) every funding interval, as % of notional
+    pub funding_rate_pct: f64,
+    pub funding_interval_hours: u64,
+    // Entry notional; paper positions have no stop, so fixed_fractional cannot size
+    pub sizing: SizingConfig,
+    pub allow_short: bool,
+    pub state_path: String,
+    // Closed trades kept in the state
+    pub max_history: usize,
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_balance: 10_000.0,
+            leverage: 5.0,
+            fee_pct: 0.05,
+            slippage_model: "spread".to_string(),
+            slippage_pct: 0.02,
+            depth_impact_pct: 0.05,
+            funding_rate_pct: 0.01,
+            funding_interval_hours: 8,
+            sizing: SizingConfig { notional: 1_000.0, ..SizingConfig::default() },
+            allow_short: true,
+            state_path: "paper_state.json".to_string(),
+            max_history: 500,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PaperPosition {
+    pub symbol: String,
+    // "long" or "short"
+    pub side: String,
+    pub quantity: f64,
+    pub entry_price: f64,
+    pub entry_time: u64,
+    pub margin: f64,
+    pub mark_price: f64,
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+    pub funding: f64,
+}
+
+impl PaperPosition {
+    fn sign(&self) -> f64 {
+        if self.side == "long" { 1.0 } else { -1.0 }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PaperTrade {
+    pub symbol: String,
+    pub side: String,
+    pub quantity: f64,
+    pub entry_price: f64,
+    pub entry_time: u64,
+    pub exit_price: f64,
+    pub exit_time: u64,
+    // Net of fees and funding
+    pub pnl: f64,
+    pub fees: f64,
+    pub funding: f64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct PaperAccount {
+    // Cash balance: deposits plus realized PnL, fees and funding
+    pub balance: f64,
+    pub realized_pnl: f64,
+    pub fees_paid: f64,
+    pub funding_paid: f64,
+    pub positions: BTreeMap<String, PaperPosition>,
+    pub history: VecDeque<PaperTrade>,
+    // Last funding boundary settled
+    pub last_funding: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PaperSnapshot {
+    pub balance: f64,
+    pub equity: f64,
+    pub used_margin: f64,
+    pub free_margin: f64,
+    pub unrealized_pnl: f64,
+    pub realized_pnl: f64,
+    pub fees_paid: f64,
+    pub funding_paid: f64,
+    pub positions: Vec<PaperPosition>,
+    pub recent_trades: Vec<PaperTrade>,
+}
+
+pub struct PaperEngine {
+    pub cfg: PaperConfig,
+    // Underlying units per contract by symbol; book sizes are in contracts
+    contract_values: HashMap<String, f64>,
+    account: PaperAccount,
+}
+
+impl PaperEngine {
+    // Resumes from the state file, or opens a fresh account when there is none
+    pub fn load(cfg: PaperConfig, contract_values: HashMap<String, f64>) -> Result<Self, String> {
+        let account = match std::fs::read_to_string(&cfg.state_path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", cfg.state_path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => PaperAccount { balance: cfg.initial_balance, ..PaperAccount::default() },
+            Err(e) => return Err(format!("{}: {}", cfg.state_path, e)),
+        };
+        Ok(Self { cfg, contract_values, account })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.account).map_err(|e| e.to_string())?;
+        std::fs::write(&self.cfg.state_path, json).map_err(|e| format!("{}: {}", self.cfg.state_path, e))
+    }
+
+    fn unrealized(&self) -> f64 {
+        self.account.positions.values().map(|p| p.unrealized_pnl).sum()
+    }
+
+    fn used_margin(&self) -> f64 {
+        self.account.positions.values().map(|p| p.margin).sum()
+    }
+
+    // Fill price for `quantity` (in coins) of `symbol` on `side` ("buy"/"sell")
+    pub fn fill_price(&self, symbol: &str, side: &str, quantity: f64, mark: f64, quote: Option<&Quote>) -> f64 {
+        let buy = side == "buy";
+        let fixed = |price: f64, pct: f64| if buy { price * (1.0 + pct / 100.0) } else { price * (1.0 - pct / 100.0) };
+        let quote = match quote {
+            Some(q) if self.cfg.slippage_model != "fixed" => q,
+            _ => return fixed(mark, self.cfg.slippage_pct),
+        };
+        let (touch, contracts) = if buy { (quote.best_ask, quote.ask_size) } else { (quote.best_bid, quote.bid_size) };
+        // Without the contract value the displayed size cannot be compared; fill at the touch
+        let size = contracts * self.contract_values.get(symbol).copied().unwrap_or(0.0);
+        if self.cfg.slippage_model == "depth" && size > 0.0 {
+            // Each multiple of the displayed size beyond the first walks the book further
+            let excess = (quantity / size - 1.0).max(0.0);
+            fixed(touch, excess * self.cfg.depth_impact_pct)
+        } else {
+            touch
+        }
+    }
+
+    // Acts on a signal: closes an opposite position, then opens in the signal's
+    // direction. `candles` are the signal timeframe's closed candles.
+    pub fn on_signal(&mut self, sig: &PipelineSignal, candles: &[Candle], quote: Option<&Quote>, now: u64) -> Result<(), String> {
+        if sig.provisional || sig.price <= 0.0 {
+            return Ok(());
+        }
+        let side = if sig.direction == "buy" { "long" } else { "short" };
+        if let Some(pos) = self.account.positions.get(&sig.symbol) {
+            if pos.side == side {
+                return Ok(());
+            }
+            self.close(&sig.symbol, sig.price, quote, now);
+        }
+        if side == "short" && !self.cfg.allow_short {
+            return Ok(());
+        }
+        let equity = self.account.balance + self.unrealized();
+        let sizing_cfg = sig.sizing.as_ref().unwrap_or(&self.cfg.sizing);
+        let input = SizingInput {
+            equity,
+            price: sig.price,
+            atr: sizing::latest_atr(candles, sizing_cfg.atr_period),
+            ..SizingInput::default()
+        };
+        let notional = sizing::notional(sizing_cfg, &input).map_err(|e| format!("{}: {}", sig.symbol, e))?;
+        if notional <= 0.0 {
+            return Err(format!("{}: {} sizing gives no position", sig.symbol, sizing_cfg.model));
+        }
+        let leverage = self.cfg.leverage.max(1.0);
+        let margin = notional / leverage;
+        let fee = notional * self.cfg.fee_pct / 100.0;
+        if margin + fee > equity - self.used_margin() {
+            return Err(format!("{}: insufficient margin for {:.2} notional", sig.symbol, notional));
+        }
+        let price = self.fill_price(&sig.symbol, sig.direction, notional / sig.price, sig.price, quote);
+        self.account.balance -= fee;
+        self.account.fees_paid += fee;
+        self.account.positions.insert(sig.symbol.clone(), PaperPosition {
+            symbol: sig.symbol.clone(),
+            side: side.to_string(),
+            quantity: notional / price,
+            entry_price: price,
+            entry_time: now,
+            margin,
+            mark_price: sig.price,
+            unrealized_pnl: 0.0,
+            fees: fee,
+            funding: 0.0,
+        });
+        Ok(())
+    }
+
+    fn close(&mut self, symbol: &str, mark: f64, quote: Option<&Quote>, now: u64) {
+        let pos = match self.account.positions.remove(symbol) {
+            Some(p) => p,
+            None => return,
+        };
+        let exit_side = if pos.side == "long" { "sell" } else { "buy" };
+        let price = self.fill_price(symbol, exit_side, pos.quantity, mark, quote);
+        let fee = pos.quantity * price * self.cfg.fee_pct / 100.0;
+        let gross = (price - pos.entry_price) * pos.quantity * pos.sign();
+        self.account.balance += gross - fee;
+        self.account.realized_pnl += gross;
+        self.account.fees_paid += fee;
+        self.account.history.push_back(PaperTrade {
+            symbol: symbol.to_string(),
+            side: pos.side.clone(),
+            quantity: pos.quantity,
+            entry_price: pos.entry_price,
+            entry_time: pos.entry_time,
+            exit_price: price,
+            exit_time: now,
+            pnl: gross - pos.fees - fee - pos.funding,
+            fees: pos.fees + fee,
+            funding: pos.funding,
+        });
+        while self.account.history.len() > self.cfg.max_history {
+            self.account.history.pop_front();
+        }
+    }
+
+    // Marks positions to the latest prices and settles funding at interval boundaries
+    pub fn mark(&mut self, prices: &HashMap<String, f64>, now: u64) {
+        for pos in self.account.positions.values_mut() {
+            if let Some(&price) = prices.get(&pos.symbol) {
+                pos.mark_price = price;
+                pos.unrealized_pnl = (price - pos.entry_price) * pos.quantity * pos.sign();
+            }
+        }
+        let interval = self.cfg.funding_interval_hours * 3600;
+        if interval == 0 {
+            return;
+        }
+        let boundary = now - now % interval;
+        let last = *self.account.last_funding.get_or_insert(boundary);
+        let settlements = (boundary - last.min(boundary)) / interval;
+        if settlements == 0 {
+            return;
+        }
+        for pos in self.account.positions.values_mut() {
+            let payment = pos.quantity * pos.mark_price * self.cfg.funding_rate_pct / 100.0 * pos.sign() * settlements as f64;
+            pos.funding += payment;
+            self.account.balance -= payment;
+            self.account.funding_paid += payment;
+        }
+        self.account.last_funding = Some(boundary);
+    }
+
+    pub fn snapshot(&self) -> PaperSnapshot {
+        let unrealized = self.unrealized();
+        let used_margin = self.used_margin();
+        let equity = self.account.balance + unrealized;
+        PaperSnapshot {
+            balance: self.account.balance,
+            equity,
+            used_margin,
+            free_margin: equity - used_margin,
+            unrealized_pnl: unrealized,
+            realized_pnl: self.account.realized_pnl,
+            fees_paid: self.account.fees_paid,
+            funding_paid: self.account.funding_paid,
+            positions: self.account.positions.values().cloned().collect(),
+            recent_trades: self.account.history.iter().rev().take(50).cloned().collect(),
+        }
+    }
+}
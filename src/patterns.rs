@@ -0,0 +1,166 @@
+// Candlestick pattern recognition over delta::Candle sequences
+use crate::delta::Candle;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub enum Pattern {
+    BullishEngulfing,
+    BearishEngulfing,
+    Hammer,
+    ShootingStar,
+    Doji,
+    MorningStar,
+    EveningStar,
+    ThreeWhiteSoldiers,
+    ThreeBlackCrows,
+    InsideBar,
+    BullishOutsideBar,
+    BearishOutsideBar,
+}
+
+impl Pattern {
+    pub fn signal(&self) -> Option<&'static str> {
+        match self {
+            Pattern::BullishEngulfing | Pattern::Hammer | Pattern::MorningStar
+            | Pattern::ThreeWhiteSoldiers | Pattern::BullishOutsideBar => Some("buy"),
+            Pattern::BearishEngulfing | Pattern::ShootingStar | Pattern::EveningStar
+            | Pattern::ThreeBlackCrows | Pattern::BearishOutsideBar => Some("sell"),
+            Pattern::Doji | Pattern::InsideBar => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct PatternConfig {
+    // Body / range at or below which a candle is a doji
+    pub doji_body_ratio: f64,
+    // Body / range at or below which a candle has a small body (hammer, star middle)
+    pub small_body_ratio: f64,
+    // Body / range at or above which a candle has a long body (soldiers, star ends)
+    pub long_body_ratio: f64,
+    // Dominant wick / body needed for hammer and shooting star
+    pub long_wick_ratio: f64,
+    // Opposite wick / range allowed for hammer and shooting star
+    pub max_opposite_wick_ratio: f64,
+    // Candles back from the crossover in which a pattern may confirm it
+    pub confirm_window: usize,
+    // Require an agreeing pattern before an EMA crossover counts as a vote
+    pub confirm_crossovers: bool,
+    // Let a pattern on the primary timeframe raise a signal on its own
+    pub standalone: bool,
+}
+
+impl Default for PatternConfig {
+    fn default() -> Self {
+        Self {
+            doji_body_ratio: 0.1,
+            small_body_ratio: 0.3,
+            long_body_ratio: 0.6,
+            long_wick_ratio: 2.0,
+            max_opposite_wick_ratio: 0.15,
+            confirm_window: 3,
+            confirm_crossovers: false,
+            standalone: false,
+        }
+    }
+}
+
+fn body(c: &Candle) -> f64 { (c.close - c.open).abs() }
+fn range(c: &Candle) -> f64 { c.high - c.low }
+fn upper_wick(c: &Candle) -> f64 { c.high - c.open.max(c.close) }
+fn lower_wick(c: &Candle) -> f64 { c.open.min(c.close) - c.low }
+fn bullish(c: &Candle) -> bool { c.close > c.open }
+fn bearish(c: &Candle) -> bool { c.close < c.open }
+fn body_ratio(c: &Candle) -> f64 { if range(c) > 0.0 { body(c) / range(c) } else { 0.0 } }
+
+// Patterns completed by the last candle of the slice
+pub fn detect_patterns(candles: &[Candle], cfg: &PatternConfig) -> Vec<Pattern> {
+    let mut found = Vec::new();
+    let n = candles.len();
+    if n == 0 {
+        return found;
+    }
+    let c = &candles[n - 1];
+    let r = range(c);
+    if r > 0.0 {
+        if body_ratio(c) <= cfg.doji_body_ratio {
+            found.push(Pattern::Doji);
+        } else if body_ratio(c) <= cfg.small_body_ratio {
+            if lower_wick(c) >= cfg.long_wick_ratio * body(c) && upper_wick(c) <= cfg.max_opposite_wick_ratio * r {
+                found.push(Pattern::Hammer);
+            }
+            if upper_wick(c) >= cfg.long_wick_ratio * body(c) && lower_wick(c) <= cfg.max_opposite_wick_ratio * r {
+                found.push(Pattern::ShootingStar);
+            }
+        }
+    }
+    if n >= 2 {
+        let p = &candles[n - 2];
+        let (c_lo, c_hi) = (c.open.min(c.close), c.open.max(c.close));
+        let (p_lo, p_hi) = (p.open.min(p.close), p.open.max(p.close));
+        if bearish(p) && bullish(c) && c_lo <= p_lo && c_hi >= p_hi && body(c) > body(p) {
+            found.push(Pattern::BullishEngulfing);
+        }
+        if bullish(p) && bearish(c) && c_lo <= p_lo && c_hi >= p_hi && body(c) > body(p) {
+            found.push(Pattern::BearishEngulfing);
+        }
+        if c.high < p.high && c.low > p.low {
+            found.push(Pattern::InsideBar);
+        }
+        if c.high > p.high && c.low < p.low {
+            if bullish(c) {
+                found.push(Pattern::BullishOutsideBar);
+            } else if bearish(c) {
+                found.push(Pattern::BearishOutsideBar);
+            }
+        }
+    }
+    if n >= 3 {
+        let (a, b) = (&candles[n - 3], &candles[n - 2]);
+        let long = |x: &Candle| body_ratio(x) >= cfg.long_body_ratio;
+        let small = |x: &Candle| body_ratio(x) <= cfg.small_body_ratio;
+        let a_mid = (a.open + a.close) / 2.0;
+        if bearish(a) && long(a) && small(b) && b.open.max(b.close) < a.close && bullish(c) && long(c) && c.close > a_mid {
+            found.push(Pattern::MorningStar);
+        }
+        if bullish(a) && long(a) && small(b) && b.open.min(b.close) > a.close && bearish(c) && long(c) && c.close < a_mid {
+            found.push(Pattern::EveningStar);
+        }
+        let three = [a, b, c];
+        if three.iter().all(|x| bullish(x) && long(x)) && a.close < b.close && b.close < c.close
+            && b.open > a.open && b.open < a.close && c.open > b.open && c.open < b.close {
+            found.push(Pattern::ThreeWhiteSoldiers);
+        }
+        if three.iter().all(|x| bearish(x) && long(x)) && a.close > b.close && b.close > c.close
+            && b.open < a.open && b.open > a.close && c.open < b.open && c.open > b.close {
+            found.push(Pattern::ThreeBlackCrows);
+        }
+    }
+    found
+}
+
+// Net direction of the patterns on the last candle, if they agree
+pub fn pattern_signal(candles: &[Candle], cfg: &PatternConfig) -> Option<&'static str> {
+    let patterns = detect_patterns(candles, cfg);
+    let buys = patterns.iter().filter(|p| p.signal() == Some("buy")).count();
+    let sells = patterns.iter().filter(|p| p.signal() == Some("sell")).count();
+    if buys > sells {
+        Some("buy")
+    } else if sells > buys {
+        Some("sell")
+    } else {
+        None
+    }
+}
+
+// Keeps a crossover signal only if an agreeing pattern appeared within the
+// last `confirm_window` candles
+pub fn confirm_crossover(signal: Option<&'static str>, candles: &[Candle], cfg: &PatternConfig) -> Option<&'static str> {
+    let signal = signal?;
+    let window = cfg.confirm_window.max(1).min(candles.len());
+    let confirmed = (0..window).any(|back| {
+        let end = candles.len() - back;
+        detect_patterns(&candles[..end], cfg).iter().any(|p| p.signal() == Some(signal))
+    });
+    if confirmed { Some(signal) } else { None }
+}
@@ -0,0 +1,303 @@
+// Per-symbol signal pipeline: candles -> rules/scripts/scoring -> gated signals
+//
+// Signals are evaluated on closed candles only, so a crossover cannot appear
+// and vanish inside a still-forming bucket. A symbol is re-evaluated once one
+// of its timeframes closes a new candle, and each timeframe's crossover votes
+// (and each rule is checked) only in the cycle its own candle closed; a 4h
+// crossover does not keep voting on every 5m close. With `intrabar_preview`
+// enabled, the forming candles are also evaluated and any extra signal is
+// marked provisional.
+use std::collections::HashMap;
+
+use crate::config::StrategyConfig;
+use crate::debounce::SignalGate;
+use crate::delta::{self, Candle};
+use crate::profile::VolumeProfile;
+use crate::ranking::RankEntry;
+use crate::regime::Regime;
+use crate::rules::{self, CompiledRule};
+use crate::scoring::{Component, ComponentScore, Score, ScoringEngine};
+use crate::script::{self, StrategyScript};
+use crate::sizing::SizingConfig;
+
+// Candles for one symbol, split per timeframe into closed and forming
+#[derive(Clone, Debug, Default)]
+pub struct SymbolCandles {
+    pub closed: HashMap<String, Vec<Candle>>,
+    pub forming: HashMap<String, Candle>,
+}
+
+impl SymbolCandles {
+    // Ticks carry the rolling 24h volume; candles get the volume traded between ticks
+    pub fn from_trades(trades: &[(f64, f64, u64)], timeframes: &[String], now: u64) -> Self {
+        let mut out = Self::default();
+        let traded: Vec<(f64, f64, u64)> = trades.iter().zip(delta::tick_volumes(trades))
+            .map(|(t, v)| (t.0, v, t.2))
+            .collect();
+        for tf in timeframes {
+            if let Some(secs) = delta::timeframe_seconds(tf) {
+                let (closed, forming) = delta::split_forming(delta::aggregate_candles(&traded, secs), secs, now);
+                out.closed.insert(tf.clone(), closed);
+                if let Some(f) = forming {
+                    out.forming.insert(tf.clone(), f);
+                }
+            }
+        }
+        out
+    }
+
+    // Closed candles plus the forming one, for intrabar previews
+    pub fn with_forming(&self) -> HashMap<String, Vec<Candle>> {
+        let mut all = self.closed.clone();
+        for (tf, candle) in &self.forming {
+            all.entry(tf.clone()).or_default().push(candle.clone());
+        }
+        all
+    }
+
+    pub fn last_price(&self, tf: &str) -> Option<f64> {
+        self.forming.get(tf).or_else(|| self.closed.get(tf)?.last()).map(|c| c.close)
+    }
+
+    pub fn last_closed_time(&self, tf: &str) -> u64 {
+        self.closed.get(tf).and_then(|c| c.last()).map_or(0, |c| c.timestamp)
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PipelineSignal {
+    pub symbol: String,
+    pub timeframe: String,
+    pub direction: &'static str,
+    pub score: f64,
+    pub breakdown: Vec<ComponentScore>,
+    pub regime: Option<Regime>,
+    // "scoring", "rule:<name>" or "script:<name>"
+    pub source: String,
+    // Raised on a forming candle and may still disappear
+    pub provisional: bool,
+    pub price: f64,
+    // Open time of the candle the signal was raised on: the newest closed
+    // candle of its timeframe, or the forming one for previews
+    pub candle_time: u64,
+    // Sizing override of the rule or script that raised it
+    #[serde(skip)]
+    pub sizing: Option<SizingConfig>,
+}
+
+// What `score` settled on for one evaluation
+struct Scored {
+    score: Score,
+    source: String,
+    timeframe: String,
+    sizing: Option<SizingConfig>,
+}
+
+pub struct Pipeline {
+    pub scoring: ScoringEngine,
+    pub rules: Vec<CompiledRule>,
+    pub scripts: Vec<StrategyScript>,
+    pub intrabar_preview: bool,
+    gate: SignalGate,
+    preview_gate: SignalGate,
+    // Last closed candle timestamp seen per (symbol, timeframe)
+    last_closed: HashMap<(String, String), u64>,
+    // Momentum percentile per symbol from the latest ranking
+    momentum: HashMap<String, f64>,
+    // Trade-based volume profiles; candles are used for symbols without one
+    profiles: HashMap<String, VolumeProfile>,
+}
+
+impl Pipeline {
+    pub fn new(scoring: ScoringEngine, rules: Vec<CompiledRule>, scripts: Vec<StrategyScript>) -> Self {
+        let intrabar_preview = scoring.config.intrabar_preview;
+        Self {
+            scoring,
+            rules,
+            scripts,
+            intrabar_preview,
+            gate: SignalGate::new(),
+            preview_gate: SignalGate::new(),
+            last_closed: HashMap::new(),
+            momentum: HashMap::new(),
+            profiles: HashMap::new(),
+        }
+    }
+
+    // Compiles the rules, loads the scripts and builds the scorer from a strategy config
+    pub fn from_config(cfg: &StrategyConfig) -> Result<Self, String> {
+        let rules = rules::compile_rules(&cfg.rules)?;
+        let scripts = script::load_scripts(&cfg.scripts)?;
+        Ok(Self::new(ScoringEngine::new(cfg.scoring.clone()), rules, scripts))
+    }
+
+    // Timeframes the scorer and rules need candles for
+    pub fn timeframes(&self) -> Vec<String> {
+        let mut tfs = self.scoring.required_timeframes();
+        for rule in &self.rules {
+            for tf in rule.timeframes() {
+                if !tfs.contains(&tf) {
+                    tfs.push(tf);
+                }
+            }
+        }
+        tfs
+    }
+
+    pub fn primary_timeframe(&self) -> String {
+        self.scoring.config.timeframes.first().cloned().unwrap_or_else(|| "5m".to_string())
+    }
+
+    // Cross-sectional inputs that a single symbol's candles cannot provide
+    pub fn set_rankings(&mut self, rankings: &[RankEntry]) {
+        self.momentum = rankings.iter().map(|e| (e.symbol.clone(), e.momentum_percentile)).collect();
+    }
+
+    pub fn set_profiles(&mut self, profiles: HashMap<String, VolumeProfile>) {
+        self.profiles = profiles;
+    }
+
+    // Profile for a symbol: the trade-based one if set, else from candles
+    pub fn profile(&self, symbol: &str, candles: &HashMap<String, Vec<Candle>>) -> Option<VolumeProfile> {
+        if let Some(p) = self.profiles.get(symbol) {
+            return Some(p.clone());
+        }
+        let cfg = &self.scoring.config.profile;
+        VolumeProfile::from_candles(candles.get(&cfg.timeframe)?, cfg)
+    }
+
+    // Built-in components plus the ones fed in from outside the symbol. With
+    // `fresh`, per-timeframe votes of timeframes that did not just close are muted.
+    fn components(&self, symbol: &str, candles: &HashMap<String, Vec<Candle>>, fresh: Option<&[String]>) -> Vec<Component> {
+        let mut components = self.scoring.components(candles);
+        if let Some(fresh) = fresh {
+            for c in components.iter_mut() {
+                if self.scoring.component_timeframe(&c.name).is_some_and(|tf| !fresh.iter().any(|f| f == tf)) {
+                    c.bull = 0.0;
+                    c.bear = 0.0;
+                }
+            }
+        }
+        // Favour longs on leaders and shorts on laggards
+        if let Some(pct) = self.momentum.get(symbol) {
+            let lead = ((pct - 50.0) / 50.0).clamp(-1.0, 1.0);
+            components.push(Component { name: "relative_strength".to_string(), bull: lead, bear: -lead });
+        }
+        // Acceptance above the value area favours longs, below it shorts
+        if let Some(profile) = self.profile(symbol, candles) {
+            let side = match profile.position() {
+                "above" => 1.0,
+                "below" => -1.0,
+                _ => 0.0,
+            };
+            components.push(Component { name: "value_area".to_string(), bull: side, bear: -side });
+        }
+        components
+    }
+
+    // Records the latest closed candles; returns the timeframes that closed a new one
+    fn new_closes(&mut self, symbol: &str, candles: &SymbolCandles) -> Vec<String> {
+        let mut fresh = Vec::new();
+        for (tf, series) in &candles.closed {
+            if let Some(last) = series.last() {
+                let prev = self.last_closed.insert((symbol.to_string(), tf.clone()), last.timestamp);
+                if prev != Some(last.timestamp) {
+                    fresh.push(tf.clone());
+                }
+            }
+        }
+        fresh
+    }
+
+    // Direction and score from rules (if configured) or the vote, before gating.
+    // The matching rule also gives the source, timeframe and sizing; the vote
+    // is "scoring" on the primary timeframe. `fresh` lists the timeframes that
+    // just closed; None (previews) lets all vote.
+    fn score(&self, symbol: &str, candles: &HashMap<String, Vec<Candle>>, fresh: Option<&[String]>) -> Option<Scored> {
+        let rule = if self.rules.is_empty() {
+            None
+        } else {
+            // Configured rules replace the built-in EMA vote
+            Some(rules::evaluate_rules(&self.rules, candles, fresh)?)
+        };
+        let regime = self.scoring.regime(candles).map(|r| r.regime);
+        let components = self.components(symbol, candles, fresh);
+        let score = self.scoring.evaluate(&components, rule.as_ref().map(|(r, _)| r.signal), regime)?;
+        Some(match rule {
+            Some((r, timeframe)) => Scored { score, source: format!("rule:{}", r.name), timeframe, sizing: r.sizing.clone() },
+            None => Scored { score, source: "scoring".to_string(), timeframe: self.primary_timeframe(), sizing: None },
+        })
+    }
+
+    pub fn evaluate(&mut self, symbol: &str, candles: &SymbolCandles, now: u64) -> Vec<PipelineSignal> {
+        let mut out = Vec::new();
+        let primary_tf = self.primary_timeframe();
+        let price = candles.last_price(&primary_tf).unwrap_or(0.0);
+        let debounce = self.scoring.config.debounce.clone();
+
+        let fresh = self.new_closes(symbol, candles);
+        if !fresh.is_empty() {
+            let regime = self.scoring.regime(&candles.closed).map(|r| r.regime);
+            // Script signals are emitted alongside the built-in strategy
+            for script in self.scripts.iter_mut() {
+                for sig in script.evaluate(symbol, &candles.closed) {
+                    if !self.gate.allow(symbol, sig.signal, now, &debounce) {
+                        continue;
+                    }
+                    out.push(PipelineSignal {
+                        symbol: symbol.to_string(),
+                        direction: sig.signal,
+                        score: sig.score,
+                        breakdown: Vec::new(),
+                        regime,
+                        source: format!("script:{}", sig.script),
+                        provisional: false,
+                        price,
+                        candle_time: candles.last_closed_time(&sig.timeframe),
+                        timeframe: sig.timeframe,
+                        sizing: script.config.sizing.clone(),
+                    });
+                }
+            }
+            if let Some(Scored { score, source, timeframe, sizing }) = self.score(symbol, &candles.closed, Some(&fresh)) {
+                if self.gate.allow(symbol, score.direction, now, &debounce) {
+                    out.push(PipelineSignal {
+                        symbol: symbol.to_string(),
+                        direction: score.direction,
+                        score: score.score,
+                        breakdown: score.breakdown,
+                        regime: score.regime,
+                        source,
+                        provisional: false,
+                        price,
+                        candle_time: candles.last_closed_time(&timeframe),
+                        timeframe,
+                        sizing,
+                    });
+                }
+            }
+        }
+
+        if self.intrabar_preview && !candles.forming.is_empty() {
+            if let Some(Scored { score, source, timeframe, sizing }) = self.score(symbol, &candles.with_forming(), None) {
+                let already = out.iter().any(|s| s.source == source && s.direction == score.direction);
+                if !already && self.preview_gate.allow(symbol, score.direction, now, &debounce) {
+                    out.push(PipelineSignal {
+                        symbol: symbol.to_string(),
+                        direction: score.direction,
+                        score: score.score,
+                        breakdown: score.breakdown,
+                        regime: score.regime,
+                        source,
+                        provisional: true,
+                        price,
+                        candle_time: candles.forming.get(&timeframe).map_or(0, |c| c.timestamp),
+                        timeframe,
+                        sizing,
+                    });
+                }
+            }
+        }
+        out
+    }
+}
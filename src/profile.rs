@@ -0,0 +1,127 @@
+// Volume-by-price profile with point of control and value area
+use crate::delta::{self, Candle};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    // Candle timeframe used when no trades are available (e.g. backtests)
+    pub timeframe: String,
+    // Window in seconds
+    pub window_sec: u64,
+    pub bins: usize,
+    // Share of volume the value area must contain
+    pub value_area_pct: f64,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self { timeframe: "5m".to_string(), window_sec: 86400, bins: 50, value_area_pct: 70.0 }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ProfileBin {
+    pub low: f64,
+    pub high: f64,
+    pub volume: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct VolumeProfile {
+    pub price: f64,
+    pub poc: f64,
+    pub vah: f64,
+    pub val: f64,
+    pub bins: Vec<ProfileBin>,
+}
+
+impl VolumeProfile {
+    // "above", "inside" or "below" the value area
+    pub fn position(&self) -> &'static str {
+        if self.price > self.vah {
+            "above"
+        } else if self.price < self.val {
+            "below"
+        } else {
+            "inside"
+        }
+    }
+
+    // Builds a profile from raw (price, volume_24h, timestamp) ticks, binning
+    // the volume traded since the previous tick
+    pub fn from_trades(trades: &[(f64, f64, u64)], now: u64, cfg: &ProfileConfig) -> Option<Self> {
+        let since = now.saturating_sub(cfg.window_sec);
+        let recent: Vec<(f64, f64)> = trades.iter().zip(delta::tick_volumes(trades))
+            .filter(|(t, _)| t.2 >= since)
+            .map(|(t, traded)| (t.0, traded))
+            .collect();
+        let price = recent.last()?.0;
+        let low = recent.iter().map(|t| t.0).fold(f64::MAX, f64::min);
+        let high = recent.iter().map(|t| t.0).fold(f64::MIN, f64::max);
+        let mut bins = empty_bins(low, high, cfg.bins)?;
+        let width = bins[0].high - bins[0].low;
+        for (p, traded) in recent {
+            let i = (((p - low) / width) as usize).min(bins.len() - 1);
+            bins[i].volume += traded;
+        }
+        Self::finish(price, bins, cfg)
+    }
+
+    // Builds a profile from candles, spreading each candle's volume evenly over its range
+    pub fn from_candles(candles: &[Candle], cfg: &ProfileConfig) -> Option<Self> {
+        let last = candles.last()?;
+        let since = last.timestamp.saturating_sub(cfg.window_sec);
+        let recent: Vec<&Candle> = candles.iter().filter(|c| c.timestamp >= since).collect();
+        let low = recent.iter().map(|c| c.low).fold(f64::MAX, f64::min);
+        let high = recent.iter().map(|c| c.high).fold(f64::MIN, f64::max);
+        let mut bins = empty_bins(low, high, cfg.bins)?;
+        let width = bins[0].high - bins[0].low;
+        for c in recent {
+            let first = (((c.low - low) / width) as usize).min(bins.len() - 1);
+            let last_bin = (((c.high - low) / width) as usize).min(bins.len() - 1);
+            let share = c.volume / (last_bin - first + 1) as f64;
+            for bin in &mut bins[first..=last_bin] {
+                bin.volume += share;
+            }
+        }
+        Self::finish(last.close, bins, cfg)
+    }
+
+    fn finish(price: f64, bins: Vec<ProfileBin>, cfg: &ProfileConfig) -> Option<Self> {
+        let total: f64 = bins.iter().map(|b| b.volume).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let poc_idx = bins.iter().enumerate()
+            .max_by(|a, b| a.1.volume.partial_cmp(&b.1.volume).unwrap_or(std::cmp::Ordering::Equal))?
+            .0;
+        // Grow the value area from the POC towards the heavier neighbour
+        let (mut lo, mut hi) = (poc_idx, poc_idx);
+        let mut covered = bins[poc_idx].volume;
+        while covered / total * 100.0 < cfg.value_area_pct && (lo > 0 || hi + 1 < bins.len()) {
+            let below = if lo > 0 { bins[lo - 1].volume } else { -1.0 };
+            let above = if hi + 1 < bins.len() { bins[hi + 1].volume } else { -1.0 };
+            if above >= below {
+                hi += 1;
+                covered += above;
+            } else {
+                lo -= 1;
+                covered += below;
+            }
+        }
+        let poc = (bins[poc_idx].low + bins[poc_idx].high) / 2.0;
+        Some(Self { price, poc, vah: bins[hi].high, val: bins[lo].low, bins })
+    }
+}
+
+fn empty_bins(low: f64, high: f64, count: usize) -> Option<Vec<ProfileBin>> {
+    if count == 0 || high <= low {
+        return None;
+    }
+    let width = (high - low) / count as f64;
+    Some((0..count).map(|i| ProfileBin {
+        low: low + width * i as f64,
+        high: low + width * (i + 1) as f64,
+        volume: 0.0,
+    }).collect())
+}
@@ -0,0 +1,80 @@
+// Relative strength ranking against BTC and an equal-weighted universe index
+use std::collections::{BTreeMap, HashMap};
+
+use crate::delta::Candle;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct RankingConfig {
+    pub timeframe: String,
+    // Lookbacks in candles of `timeframe`
+    pub lookbacks: Vec<usize>,
+    pub benchmark: String,
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self { timeframe: "1h".to_string(), lookbacks: vec![24, 72, 168], benchmark: "BTCUSD".to_string() }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RankEntry {
+    pub symbol: String,
+    pub rank: usize, // 1 = strongest
+    // Keyed by lookback in candles
+    pub returns: BTreeMap<usize, f64>,
+    pub rs_benchmark: BTreeMap<usize, f64>,
+    pub rs_index: BTreeMap<usize, f64>,
+    // Percentile (0-100) of the mean relative strength vs the index
+    pub momentum_percentile: f64,
+}
+
+// Fractional return over the last `lookback` candles
+fn period_return(candles: &[Candle], lookback: usize) -> Option<f64> {
+    let last = candles.last()?.close;
+    let first = candles.get(candles.len().checked_sub(lookback + 1)?)?.close;
+    if first > 0.0 { Some(last / first - 1.0) } else { None }
+}
+
+fn relative(r: f64, base: f64) -> f64 {
+    (1.0 + r) / (1.0 + base) - 1.0
+}
+
+// Ranks symbols by momentum; symbols without enough history for every lookback are left out
+pub fn rank(candles: &HashMap<String, Vec<Candle>>, cfg: &RankingConfig) -> Vec<RankEntry> {
+    let mut returns: Vec<(String, BTreeMap<usize, f64>)> = candles.iter()
+        .filter_map(|(symbol, series)| {
+            let r: Option<BTreeMap<usize, f64>> = cfg.lookbacks.iter()
+                .map(|&lb| Some((lb, period_return(series, lb)?)))
+                .collect();
+            Some((symbol.clone(), r?))
+        })
+        .collect();
+    if returns.is_empty() || cfg.lookbacks.is_empty() {
+        return Vec::new();
+    }
+    returns.sort_by(|a, b| a.0.cmp(&b.0));
+    let benchmark = returns.iter().find(|(s, _)| *s == cfg.benchmark).map(|(_, r)| r.clone());
+    let index: BTreeMap<usize, f64> = cfg.lookbacks.iter()
+        .map(|lb| (*lb, returns.iter().map(|(_, r)| r[lb]).sum::<f64>() / returns.len() as f64))
+        .collect();
+
+    let mut entries: Vec<RankEntry> = returns.into_iter().map(|(symbol, r)| {
+        let rs_index: BTreeMap<usize, f64> = r.iter().map(|(lb, v)| (*lb, relative(*v, index[lb]))).collect();
+        let rs_benchmark = match &benchmark {
+            Some(b) => r.iter().map(|(lb, v)| (*lb, relative(*v, b[lb]))).collect(),
+            None => BTreeMap::new(),
+        };
+        RankEntry { symbol, rank: 0, returns: r, rs_benchmark, rs_index, momentum_percentile: 0.0 }
+    }).collect();
+
+    let strength = |e: &RankEntry| e.rs_index.values().sum::<f64>() / e.rs_index.len() as f64;
+    entries.sort_by(|a, b| strength(b).partial_cmp(&strength(a)).unwrap_or(std::cmp::Ordering::Equal));
+    let n = entries.len();
+    for (i, e) in entries.iter_mut().enumerate() {
+        e.rank = i + 1;
+        e.momentum_percentile = if n > 1 { (n - 1 - i) as f64 / (n - 1) as f64 * 100.0 } else { 50.0 };
+    }
+    entries
+}
@@ -0,0 +1,94 @@
+// Market regime classification from ADX, ATR percentile and EMA slope
+use crate::delta::Candle;
+use crate::ema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Regime {
+    TrendingUp,
+    TrendingDown,
+    Ranging,
+    HighVolatility,
+}
+
+impl Regime {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Regime::TrendingUp => "trending_up",
+            Regime::TrendingDown => "trending_down",
+            Regime::Ranging => "ranging",
+            Regime::HighVolatility => "high_volatility",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct RegimeConfig {
+    pub adx_period: usize,
+    // ADX at or above this is a trend
+    pub adx_trend: f64,
+    pub atr_period: usize,
+    // Candles the current ATR is ranked against
+    pub atr_lookback: usize,
+    // ATR percentile at or above this is high volatility
+    pub high_vol_percentile: f64,
+    pub slope_period: usize,
+    // Candles over which the EMA slope is measured
+    pub slope_lookback: usize,
+}
+
+impl Default for RegimeConfig {
+    fn default() -> Self {
+        Self {
+            adx_period: 14,
+            adx_trend: 25.0,
+            atr_period: 14,
+            atr_lookback: 100,
+            high_vol_percentile: 90.0,
+            slope_period: 26,
+            slope_lookback: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct RegimeReading {
+    pub regime: Regime,
+    pub adx: f64,
+    pub atr_percentile: f64,
+    // EMA change over slope_lookback candles, % of price
+    pub ema_slope_pct: f64,
+}
+
+pub fn classify(candles: &[Candle], cfg: &RegimeConfig) -> Option<RegimeReading> {
+    let warmup = (2 * cfg.adx_period).max(cfg.atr_period).max(cfg.slope_period).max(cfg.slope_lookback + 1);
+    if candles.len() < warmup || cfg.adx_period == 0 || cfg.atr_period == 0 {
+        return None;
+    }
+    let highs: Vec<f64> = candles.iter().map(|c| c.high).collect();
+    let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+
+    let adx = *ema::calculate_adx(&highs, &lows, &closes, cfg.adx_period).last()?;
+    let atr = ema::calculate_atr(&highs, &lows, &closes, cfg.atr_period);
+    let recent = &atr[(cfg.atr_period - 1).max(atr.len().saturating_sub(cfg.atr_lookback))..];
+    let current = *recent.last()?;
+    let atr_percentile = recent.iter().filter(|&&a| a <= current).count() as f64 / recent.len() as f64 * 100.0;
+
+    let ema_line = ema::calculate_ema(&closes, cfg.slope_period);
+    let last = *ema_line.last()?;
+    let before = ema_line[ema_line.len() - 1 - cfg.slope_lookback];
+    let ema_slope_pct = if before != 0.0 { (last - before) / before * 100.0 } else { 0.0 };
+
+    let regime = if atr_percentile >= cfg.high_vol_percentile {
+        Regime::HighVolatility
+    } else if adx >= cfg.adx_trend && ema_slope_pct > 0.0 {
+        Regime::TrendingUp
+    } else if adx >= cfg.adx_trend && ema_slope_pct < 0.0 {
+        Regime::TrendingDown
+    } else {
+        Regime::Ranging
+    };
+    Some(RegimeReading { regime, adx, atr_percentile, ema_slope_pct })
+}
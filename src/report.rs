@@ -0,0 +1,135 @@
+// Self-contained HTML backtest report with inline SVG equity and drawdown charts
+use crate::backtest::{BacktestResult, EquityPoint};
+use crate::metrics::{self, Metrics, TradeStats};
+use crate::montecarlo::MonteCarloReport;
+
+const CHART_W: f64 = 860.0;
+const CHART_H: f64 = 200.0;
+
+// Polyline path for `values` scaled into the chart box
+fn svg_path(values: &[f64], lo: f64, hi: f64) -> String {
+    let span = if hi > lo { hi - lo } else { 1.0 };
+    let step = if values.len() > 1 { CHART_W / (values.len() - 1) as f64 } else { 0.0 };
+    values.iter().enumerate()
+        .map(|(i, v)| format!("{}{:.1},{:.1}", if i == 0 { "M" } else { " L" }, i as f64 * step, CHART_H - (v - lo) / span * CHART_H))
+        .collect()
+}
+
+// Keeps at most `max` evenly spaced points so large backtests stay small
+fn downsample<T: Clone>(points: &[T], max: usize) -> Vec<T> {
+    if points.len() <= max || max < 2 {
+        return points.to_vec();
+    }
+    let step = (points.len() - 1) as f64 / (max - 1) as f64;
+    (0..max).map(|i| points[(i as f64 * step).round() as usize].clone()).collect()
+}
+
+fn chart(title: &str, values: &[f64], lo: f64, hi: f64, color: &str) -> String {
+    format!(
+        "<h2>{title}</h2><svg viewBox='0 0 {w} {h}' width='100%' height='{h}' preserveAspectRatio='none'>\
+         <path d='{path}' fill='none' stroke='{color}' stroke-width='1.5' vector-effect='non-scaling-stroke'/></svg>\
+         <div class='axis'>{hi:.2} / {lo:.2}</div>",
+        title = title, w = CHART_W, h = CHART_H, path = svg_path(values, lo, hi), color = color, hi = hi, lo = lo,
+    )
+}
+
+fn stats_rows(groups: &std::collections::BTreeMap<String, TradeStats>) -> String {
+    groups.iter().map(|(name, s)| format!(
+        "<tr><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.1}%</td><td>{:.2}</td><td>{:.2}</td><td>{}</td></tr>",
+        name, s.trades, s.net_pnl, s.win_rate, s.profit_factor, s.expectancy, metrics::format_duration(s.avg_hold),
+    )).collect()
+}
+
+const STATS_HEADER: &str = "<tr><th></th><th>Trades</th><th>Net PnL</th><th>Win rate</th><th>Profit factor</th><th>Expectancy</th><th>Avg hold</th></tr>";
+
+fn montecarlo_section(mc: &MonteCarloReport) -> String {
+    let rows: String = mc.methods.iter().map(|(method, s)| format!(
+        "<tr><td>{}</td><td>{:.2} / {:.2} / {:.2}</td><td>{:.2}% / {:.2}% / {:.2}%</td><td>{:.1}%</td></tr>",
+        method, s.final_equity.lower, s.final_equity.median, s.final_equity.upper,
+        s.max_drawdown_pct.lower, s.max_drawdown_pct.median, s.max_drawdown_pct.upper, s.risk_of_ruin_pct,
+    )).collect();
+    format!(
+        "<h2>Monte Carlo ({} paths, {:.0}% intervals, ruin at {:.0}% drawdown)</h2>\
+         <table><tr><th>Method</th><th>Final equity (low / median / high)</th><th>Max drawdown (low / median / high)</th><th>Risk of ruin</th></tr>{}</table>",
+        mc.simulations, mc.confidence_pct, mc.ruin_drawdown_pct, rows,
+    )
+}
+
+pub fn render(result: &BacktestResult, m: &Metrics, mc: Option<&MonteCarloReport>) -> String {
+    let curve: Vec<EquityPoint> = downsample(&result.equity, 2000);
+    let equity: Vec<f64> = curve.iter().map(|p| p.equity).collect();
+    let dd: Vec<f64> = metrics::drawdowns(&curve).iter().map(|d| -d).collect();
+    let lo = equity.iter().cloned().fold(f64::MAX, f64::min);
+    let hi = equity.iter().cloned().fold(f64::MIN, f64::max);
+    let dd_lo = dd.iter().cloned().fold(0.0, f64::min);
+
+    let summary = [
+        ("Total return", format!("{:.2}%", m.total_return_pct)),
+        ("CAGR", format!("{:.2}%", m.cagr_pct)),
+        ("Sharpe", format!("{:.2}", m.sharpe)),
+        ("Sortino", format!("{:.2}", m.sortino)),
+        ("Calmar", format!("{:.2}", m.calmar)),
+        ("Max drawdown", format!("{:.2}%", m.max_drawdown_pct)),
+        ("Max drawdown duration", metrics::format_duration(m.max_drawdown_duration as f64)),
+        ("Win rate", format!("{:.1}%", m.trade_stats.win_rate)),
+        ("Profit factor", format!("{:.2}", m.trade_stats.profit_factor)),
+        ("Expectancy", format!("{:.2}", m.trade_stats.expectancy)),
+        ("Average hold", metrics::format_duration(m.trade_stats.avg_hold)),
+        ("Exposure", format!("{:.1}%", m.exposure_pct)),
+        ("Trades", m.trade_stats.trades.to_string()),
+        ("Equity", format!("{:.2} → {:.2}", m.initial_equity, m.final_equity)),
+    ];
+    let summary_rows: String = summary.iter().map(|(k, v)| format!("<tr><td>{}</td><td>{}</td></tr>", k, v)).collect();
+    let trade_rows: String = result.trades.iter().map(|t| format!(
+        "<tr><td>{}</td><td>{}</td><td class='{}'>{}</td><td>{}</td><td>{:.4}</td><td>{}</td><td>{:.4}</td><td class='{}'>{:.2}</td><td>{}</td></tr>",
+        t.symbol, t.timeframe, if t.side == "long" { "buy" } else { "sell" }, t.side, t.entry_time, t.entry_price,
+        t.exit_time, t.exit_price, if t.pnl > 0.0 { "buy" } else { "sell" }, t.pnl, t.exit_reason,
+    )).collect();
+
+    format!(r#"<!DOCTYPE html>
+<html lang='en'>
+<head>
+    <meta charset='UTF-8'>
+    <title>Backtest Report</title>
+    <style>
+        body {{ font-family: 'Segoe UI', Arial, sans-serif; background: #181818; color: #f5f5f5; margin: 0; }}
+        header {{ background: #222; padding: 0.5rem; text-align: center; font-size: 1.2rem; color: #ffd700; }}
+        .container {{ max-width: 900px; margin: 1rem auto; padding: 1rem; background: #222; border-radius: 8px; box-shadow: 0 2px 8px #000a; }}
+        h2 {{ color: #ffd700; font-size: 1rem; margin-bottom: 0.5rem; }}
+        table {{ width: 100%; border-collapse: collapse; margin-top: 1rem; font-size: 0.85rem; }}
+        th, td {{ padding: 0.3rem 0.4rem; border-bottom: 1px solid #444; text-align: left; }}
+        th {{ background: #333; color: #ffd700; font-size: 0.9rem; }}
+        svg {{ background: #1c1c1c; border-radius: 4px; }}
+        .axis {{ color: #aaa; font-size: 0.75rem; text-align: right; }}
+        .buy {{ color: #00ff99; font-weight: bold; }}
+        .sell {{ color: #ff4d4d; font-weight: bold; }}
+    </style>
+</head>
+<body>
+    <header>Backtest Report</header>
+    <div class='container'>
+        <h2>Summary</h2>
+        <table>{summary_rows}</table>
+        {equity_chart}
+        {dd_chart}
+        {montecarlo}
+        <h2>By symbol</h2>
+        <table>{header}{by_symbol}</table>
+        <h2>By timeframe</h2>
+        <table>{header}{by_timeframe}</table>
+        <h2>Trades</h2>
+        <table><tr><th>Symbol</th><th>TF</th><th>Side</th><th>Entry time</th><th>Entry</th><th>Exit time</th><th>Exit</th><th>PnL</th><th>Exit reason</th></tr>{trade_rows}</table>
+    </div>
+</body>
+</html>
+"#,
+        summary_rows = summary_rows,
+        equity_chart = chart("Equity", &equity, lo, hi, "#00ff99"),
+        dd_chart = chart("Drawdown (%)", &dd, dd_lo, 0.0, "#ff4d4d"),
+        montecarlo = mc.map(montecarlo_section).unwrap_or_default(),
+        header = STATS_HEADER,
+        by_symbol = stats_rows(&m.by_symbol),
+        by_timeframe = stats_rows(&m.by_timeframe),
+        trade_rows = trade_rows,
+    )
+}
@@ -0,0 +1,330 @@
+// Portfolio risk checks that every new entry passes before it is sent
+//
+// An entry is rejected when it would exceed the open position count, gross or
+// net exposure, the per-symbol notional, leverage, or the combined same-side
+// exposure of coins whose returns correlate with it above the threshold.
+// Returns are taken over candles both coins have, and a correlation that
+// cannot be measured (too little shared history, a flat series) counts as
+// correlated. Reduce-only orders are never checked. A loss of
+// daily_loss_limit_pct from the day's starting equity trips the kill switch,
+// which blocks entries until the next UTC day; the executor then cancels
+// working entries and, if configured, flattens. Rejections are kept with
+// their reason for the dashboard.
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct RiskConfig {
+    pub max_open_positions: usize,
+    // Notional limits in quote currency
+    pub max_gross_exposure: f64,
+    pub max_net_exposure: f64,
+    pub max_symbol_notional: f64,
+    // Gross exposure over equity
+    pub max_leverage: f64,
+    // Returns of this timeframe's closes are correlated
+    pub correlation_timeframe: String,
+    pub correlation_lookback: usize,
+    pub correlation_threshold: f64,
+    // Fewer aligned returns than this leave the correlation unknown
+    pub correlation_min_returns: usize,
+    // Same-side notional of a coin and everything correlated with it
+    pub max_correlated_exposure: f64,
+    pub daily_loss_limit_pct: f64,
+    // Close every position when the kill switch trips
+    pub flatten_on_kill: bool,
+    pub max_rejections: usize,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            max_open_positions: 5,
+            max_gross_exposure: 10_000.0,
+            max_net_exposure: 5_000.0,
+            max_symbol_notional: 2_500.0,
+            max_leverage: 3.0,
+            correlation_timeframe: "1h".to_string(),
+            correlation_lookback: 48,
+            correlation_threshold: 0.8,
+            correlation_min_returns: 24,
+            max_correlated_exposure: 5_000.0,
+            daily_loss_limit_pct: 5.0,
+            flatten_on_kill: false,
+            max_rejections: 200,
+        }
+    }
+}
+
+// Signed notional of one open position (positive for longs)
+#[derive(Clone, Debug)]
+pub struct Exposure {
+    pub symbol: String,
+    pub notional: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Rejection {
+    pub time: u64,
+    pub symbol: String,
+    pub side: String,
+    pub notional: f64,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct KillSwitch {
+    pub triggered_at: u64,
+    pub reason: String,
+}
+
+pub enum KillSwitchChange {
+    Tripped(KillSwitch),
+    Reset,
+}
+
+// Survives restarts so a restart cannot reset the day's loss
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct RiskState {
+    // UTC day number of day_start_equity
+    pub day: u64,
+    pub day_start_equity: f64,
+    pub kill_switch: Option<KillSwitch>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct RiskSnapshot {
+    pub kill_switch: Option<KillSwitch>,
+    pub equity: f64,
+    pub day_start_equity: f64,
+    pub day_pnl_pct: f64,
+    pub open_positions: usize,
+    pub gross_exposure: f64,
+    pub net_exposure: f64,
+    pub leverage: f64,
+    // Newest first
+    pub rejections: Vec<Rejection>,
+}
+
+// Pearson correlation of simple returns over the last `lookback` candles both
+// (timestamp, close) series have; None with fewer than `min_returns` returns
+pub fn correlation(a: &[(u64, f64)], b: &[(u64, f64)], lookback: usize, min_returns: usize) -> Option<f64> {
+    let b: HashMap<u64, f64> = b.iter().copied().collect();
+    let pairs: Vec<(f64, f64)> = a.iter().filter_map(|(t, x)| Some((*x, *b.get(t)?))).collect();
+    let pairs = &pairs[pairs.len().saturating_sub(lookback + 1)..];
+    let ret = |x: f64, y: f64| if x > 0.0 { y / x - 1.0 } else { 0.0 };
+    let (ra, rb): (Vec<f64>, Vec<f64>) = pairs.windows(2).map(|w| (ret(w[0].0, w[1].0), ret(w[0].1, w[1].1))).unzip();
+    if ra.len() < min_returns.max(3) {
+        return None;
+    }
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+    let (ma, mb) = (mean(&ra), mean(&rb));
+    let cov: f64 = ra.iter().zip(&rb).map(|(x, y)| (x - ma) * (y - mb)).sum();
+    let va: f64 = ra.iter().map(|x| (x - ma).powi(2)).sum();
+    let vb: f64 = rb.iter().map(|y| (y - mb).powi(2)).sum();
+    if va <= 0.0 || vb <= 0.0 { None } else { Some(cov / (va * vb).sqrt()) }
+}
+
+pub struct RiskManager {
+    pub cfg: RiskConfig,
+    pub state: RiskState,
+    equity: f64,
+    // Recent (candle open time, close) per symbol, for correlations
+    closes: HashMap<String, Vec<(u64, f64)>>,
+    rejections: VecDeque<Rejection>,
+}
+
+impl RiskManager {
+    pub fn new(cfg: RiskConfig, state: RiskState) -> Self {
+        Self { cfg, state, equity: 0.0, closes: HashMap::new(), rejections: VecDeque::new() }
+    }
+
+    pub fn set_closes(&mut self, symbol: &str, closes: Vec<(u64, f64)>) {
+        self.closes.insert(symbol.to_string(), closes);
+    }
+
+    pub fn last_close(&self, symbol: &str) -> Option<f64> {
+        self.closes.get(symbol)?.last().map(|c| c.1)
+    }
+
+    fn day_pnl_pct(&self) -> f64 {
+        if self.state.day_start_equity > 0.0 { (self.equity / self.state.day_start_equity - 1.0) * 100.0 } else { 0.0 }
+    }
+
+    // Tracks equity against the day's start; reports when the kill switch trips or resets
+    pub fn update_equity(&mut self, equity: f64, now: u64) -> Option<KillSwitchChange> {
+        self.equity = equity;
+        let day = now / 86_400;
+        if day != self.state.day || self.state.day_start_equity <= 0.0 {
+            self.state.day = day;
+            self.state.day_start_equity = equity;
+            if self.state.kill_switch.take().is_some() {
+                return Some(KillSwitchChange::Reset);
+            }
+        }
+        let pnl = self.day_pnl_pct();
+        if self.state.kill_switch.is_none() && self.cfg.daily_loss_limit_pct > 0.0 && pnl <= -self.cfg.daily_loss_limit_pct {
+            let kill = KillSwitch { triggered_at: now, reason: format!("daily loss {:.2}% exceeds {:.2}%", -pnl, self.cfg.daily_loss_limit_pct) };
+            self.state.kill_switch = Some(kill.clone());
+            return Some(KillSwitchChange::Tripped(kill));
+        }
+        None
+    }
+
+    fn evaluate(&self, symbol: &str, side: &str, notional: f64, positions: &[Exposure]) -> Result<(), String> {
+        if let Some(kill) = &self.state.kill_switch {
+            return Err(format!("kill switch active ({})", kill.reason));
+        }
+        if self.equity <= 0.0 {
+            return Err("account equity unknown".to_string());
+        }
+        let signed = if side == "buy" { notional } else { -notional };
+        if positions.len() >= self.cfg.max_open_positions {
+            return Err(format!("{} open positions (max {})", positions.len(), self.cfg.max_open_positions));
+        }
+        let symbol_notional = positions.iter().filter(|p| p.symbol == symbol).map(|p| p.notional).sum::<f64>() + signed;
+        if symbol_notional.abs() > self.cfg.max_symbol_notional {
+            return Err(format!("symbol notional {:.2} exceeds {:.2}", symbol_notional.abs(), self.cfg.max_symbol_notional));
+        }
+        let gross = positions.iter().map(|p| p.notional.abs()).sum::<f64>() + notional;
+        if gross > self.cfg.max_gross_exposure {
+            return Err(format!("gross exposure {:.2} exceeds {:.2}", gross, self.cfg.max_gross_exposure));
+        }
+        let net = positions.iter().map(|p| p.notional).sum::<f64>() + signed;
+        if net.abs() > self.cfg.max_net_exposure {
+            return Err(format!("net exposure {:.2} exceeds {:.2}", net.abs(), self.cfg.max_net_exposure));
+        }
+        if gross / self.equity > self.cfg.max_leverage {
+            return Err(format!("leverage {:.2}x exceeds {:.2}x", gross / self.equity, self.cfg.max_leverage));
+        }
+        // Fails closed: a pair whose correlation cannot be measured counts as correlated
+        let own = self.closes.get(symbol);
+        let correlated: f64 = positions.iter()
+            .filter(|p| p.symbol != symbol && p.notional.signum() == signed.signum())
+            .filter(|p| match (own, self.closes.get(&p.symbol)) {
+                (Some(a), Some(b)) => correlation(a, b, self.cfg.correlation_lookback, self.cfg.correlation_min_returns)
+                    .is_none_or(|r| r >= self.cfg.correlation_threshold),
+                _ => true,
+            })
+            .map(|p| p.notional.abs())
+            .sum();
+        let group = correlated + symbol_notional.abs();
+        if correlated > 0.0 && group > self.cfg.max_correlated_exposure {
+            return Err(format!("correlated exposure {:.2} exceeds {:.2}", group, self.cfg.max_correlated_exposure));
+        }
+        Ok(())
+    }
+
+    // Checks a new entry of `notional` on `side` ("buy"/"sell"); rejections are recorded
+    pub fn check(&mut self, symbol: &str, side: &str, notional: f64, positions: &[Exposure], now: u64) -> Result<(), String> {
+        let result = self.evaluate(symbol, side, notional, positions);
+        if let Err(reason) = &result {
+            self.rejections.push_back(Rejection { time: now, symbol: symbol.to_string(), side: side.to_string(), notional, reason: reason.clone() });
+            while self.rejections.len() > self.cfg.max_rejections {
+                self.rejections.pop_front();
+            }
+        }
+        result
+    }
+
+    pub fn snapshot(&self, positions: &[Exposure]) -> RiskSnapshot {
+        let gross: f64 = positions.iter().map(|p| p.notional.abs()).sum();
+        RiskSnapshot {
+            kill_switch: self.state.kill_switch.clone(),
+            equity: self.equity,
+            day_start_equity: self.state.day_start_equity,
+            day_pnl_pct: self.day_pnl_pct(),
+            open_positions: positions.len(),
+            gross_exposure: gross,
+            net_exposure: positions.iter().map(|p| p.notional).sum(),
+            leverage: if self.equity > 0.0 { gross / self.equity } else { 0.0 },
+            rejections: self.rejections.iter().rev().take(50).cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> RiskManager {
+        let mut m = RiskManager::new(RiskConfig::default(), RiskState::default());
+        m.update_equity(10_000.0, 0);
+        m
+    }
+
+    fn exposure(symbol: &str, notional: f64) -> Exposure {
+        Exposure { symbol: symbol.to_string(), notional }
+    }
+
+    // Closes at hourly timestamps following `moves` in %
+    fn series(start: u64, moves: &[f64]) -> Vec<(u64, f64)> {
+        let mut price = 100.0;
+        moves.iter().enumerate().map(|(i, m)| {
+            price *= 1.0 + m / 100.0;
+            (start + i as u64 * 3600, price)
+        }).collect()
+    }
+
+    fn wiggle(n: usize) -> Vec<f64> {
+        (0..n).map(|i| ((i * 7 % 5) as f64 - 2.0) * 0.3).collect()
+    }
+
+    #[test]
+    fn limits_reject_with_reason() {
+        let mut m = manager();
+        assert!(m.check("BTCUSD", "buy", 2_000.0, &[], 1).is_ok());
+        let err = m.check("BTCUSD", "buy", 1_000.0, &[exposure("BTCUSD", 2_000.0)], 2).unwrap_err();
+        assert!(err.starts_with("symbol notional"));
+        let held: Vec<Exposure> = (0..4).map(|i| exposure(&format!("C{}", i), 2_400.0)).collect();
+        assert!(m.check("ETHUSD", "sell", 1_000.0, &held, 3).unwrap_err().starts_with("gross exposure"));
+        assert!(m.check("ETHUSD", "sell", 1_000.0, &held[..2], 4).is_ok());
+        let net: Vec<Exposure> = (0..2).map(|i| exposure(&format!("C{}", i), 2_400.0)).collect();
+        assert!(m.check("ETHUSD", "buy", 1_000.0, &[&net[..], &[exposure("D", 100.0)]].concat(), 5).unwrap_err().starts_with("net exposure"));
+        assert_eq!(m.snapshot(&[]).rejections.len(), 3);
+    }
+
+    #[test]
+    fn kill_switch_trips_on_daily_loss_and_resets_next_day() {
+        let mut m = manager();
+        assert!(m.update_equity(9_600.0, 100).is_none());
+        assert!(matches!(m.update_equity(9_490.0, 200), Some(KillSwitchChange::Tripped(_))));
+        assert!(m.update_equity(9_000.0, 300).is_none());
+        assert!(m.check("BTCUSD", "buy", 100.0, &[], 300).unwrap_err().starts_with("kill switch"));
+        assert!(matches!(m.update_equity(9_000.0, 86_400), Some(KillSwitchChange::Reset)));
+        assert!(m.check("BTCUSD", "buy", 100.0, &[], 86_400).is_ok());
+    }
+
+    #[test]
+    fn correlation_aligns_on_timestamps() {
+        let moves = wiggle(40);
+        let a = series(0, &moves);
+        let mut b = series(0, &moves);
+        // b misses a candle; the remaining ones still line up
+        b.remove(10);
+        assert!(correlation(&a, &b, 48, 20).unwrap() > 0.99);
+        // Shifted by one candle the same moves no longer line up
+        let shifted = series(3600, &moves);
+        assert!(correlation(&a, &shifted, 48, 20).unwrap() < 0.5);
+        // Too little shared history leaves it unknown
+        assert!(correlation(&a, &series(30 * 3600, &moves), 48, 20).is_none());
+    }
+
+    #[test]
+    fn unknown_correlation_counts_as_correlated() {
+        let mut m = manager();
+        let moves = wiggle(40);
+        m.set_closes("BTCUSD", series(0, &moves));
+        m.set_closes("ETHUSD", series(0, &moves));
+        m.set_closes("SOLUSD", series(0, &moves.iter().map(|x| -x).collect::<Vec<_>>()));
+        let held = [exposure("ETHUSD", 2_000.0), exposure("SOLUSD", 2_000.0)];
+        // ETH moves with BTC: 2000 + 2000 correlated, below the 5000 cap
+        assert!(m.check("BTCUSD", "buy", 2_000.0, &held[..1], 1).is_ok());
+        // SOL moves against BTC and does not count
+        m.cfg.max_correlated_exposure = 3_000.0;
+        assert!(m.check("BTCUSD", "buy", 2_000.0, &held[1..], 1).is_ok());
+        assert!(m.check("BTCUSD", "buy", 2_000.0, &held[..1], 1).unwrap_err().starts_with("correlated exposure"));
+        // A coin without history is assumed correlated with everything
+        assert!(m.check("XRPUSD", "buy", 2_000.0, &held[1..], 1).unwrap_err().starts_with("correlated exposure"));
+    }
+}
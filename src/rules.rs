@@ -0,0 +1,553 @@
+// Declarative strategy rules, e.g.
+//   crosses_above(ema(12), ema(26)) on 1h and rsi(14) < 70
+// Rules are parsed once at startup and evaluated against per-timeframe candles.
+// "on <tf>" applies to the whole and-chain it appears in (both conditions above
+// run on 1h); a condition with its own "on" keeps it, and conditions in a chain
+// without one use the rule's timeframe.
+use std::collections::HashMap;
+
+use crate::delta::{self, Candle};
+use crate::ema;
+use crate::sizing::SizingConfig;
+
+// A value series computed from a timeframe's candles
+#[derive(Clone, Debug, PartialEq)]
+pub enum Series {
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    Ema(usize),
+    Sma(usize),
+    Rsi(usize),
+    Macd,
+    MacdSignal,
+    Const(f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CmpOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    CrossesAbove(Series, Series, Option<String>),
+    CrossesBelow(Series, Series, Option<String>),
+    Compare(Series, CmpOp, Series, Option<String>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+// One rule as written in the strategy file
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct RuleConfig {
+    pub name: String,
+    pub signal: String, // "buy" or "sell"
+    pub when: String,
+    // Timeframe for conditions without an explicit "on <tf>"
+    #[serde(default = "default_timeframe")]
+    pub timeframe: String,
+    // Sizing for this rule's signals instead of the engine's own
+    #[serde(default)]
+    pub sizing: Option<SizingConfig>,
+}
+
+fn default_timeframe() -> String {
+    "5m".to_string()
+}
+
+#[derive(Clone, Debug)]
+pub struct CompiledRule {
+    pub name: String,
+    pub signal: &'static str,
+    pub condition: Condition,
+    pub timeframe: String,
+    pub sizing: Option<SizingConfig>,
+}
+
+impl CompiledRule {
+    // Timeframes this rule needs candles for
+    pub fn timeframes(&self) -> Vec<String> {
+        let mut tfs = Vec::new();
+        collect_timeframes(&self.condition, &mut tfs);
+        tfs
+    }
+
+    pub fn matches(&self, candles: &HashMap<String, Vec<Candle>>) -> bool {
+        evaluate(&self.condition, candles)
+    }
+}
+
+pub fn compile_rules(rules: &[RuleConfig]) -> Result<Vec<CompiledRule>, String> {
+    rules.iter().map(compile_rule).collect()
+}
+
+pub fn compile_rule(rule: &RuleConfig) -> Result<CompiledRule, String> {
+    let signal = match rule.signal.as_str() {
+        "buy" => "buy",
+        "sell" => "sell",
+        other => return Err(format!("rule '{}': signal must be buy or sell, got '{}'", rule.name, other)),
+    };
+    if delta::timeframe_seconds(&rule.timeframe).is_none() {
+        return Err(format!("rule '{}': invalid timeframe '{}'", rule.name, rule.timeframe));
+    }
+    let mut condition = parse_condition(&rule.when).map_err(|e| format!("rule '{}': {}", rule.name, e))?;
+    set_default_timeframe(&mut condition, &rule.timeframe);
+    Ok(CompiledRule { name: rule.name.clone(), signal, condition, timeframe: rule.timeframe.clone(), sizing: rule.sizing.clone() })
+}
+
+// Returns the first matching rule and the timeframe it fired on. With `fresh`,
+// only rules on a timeframe that just closed a candle are checked, so a
+// condition does not match again on every close of some other timeframe. The
+// rule's own timeframe is preferred when it is one of those.
+pub fn evaluate_rules<'a>(rules: &'a [CompiledRule], candles: &HashMap<String, Vec<Candle>>, fresh: Option<&[String]>) -> Option<(&'a CompiledRule, String)> {
+    let closed = |tf: &String| fresh.is_none_or(|f| f.contains(tf));
+    rules.iter().find_map(|r| {
+        let tfs = r.timeframes();
+        let tf = if tfs.contains(&r.timeframe) && closed(&r.timeframe) {
+            r.timeframe.clone()
+        } else {
+            tfs.into_iter().find(|tf| closed(tf))?
+        };
+        r.matches(candles).then_some((r, tf))
+    })
+}
+
+fn set_default_timeframe(cond: &mut Condition, tf: &str) {
+    match cond {
+        Condition::CrossesAbove(_, _, t) | Condition::CrossesBelow(_, _, t) | Condition::Compare(_, _, _, t) => {
+            if t.is_none() {
+                *t = Some(tf.to_string());
+            }
+        }
+        Condition::And(a, b) | Condition::Or(a, b) => {
+            set_default_timeframe(a, tf);
+            set_default_timeframe(b, tf);
+        }
+        Condition::Not(c) => set_default_timeframe(c, tf),
+    }
+}
+
+fn collect_timeframes(cond: &Condition, out: &mut Vec<String>) {
+    match cond {
+        Condition::CrossesAbove(_, _, t) | Condition::CrossesBelow(_, _, t) | Condition::Compare(_, _, _, t) => {
+            if let Some(t) = t {
+                if !out.contains(t) {
+                    out.push(t.clone());
+                }
+            }
+        }
+        Condition::And(a, b) | Condition::Or(a, b) => {
+            collect_timeframes(a, out);
+            collect_timeframes(b, out);
+        }
+        Condition::Not(c) => collect_timeframes(c, out),
+    }
+}
+
+// ---- Evaluation ----
+
+// Computes a series over candles; None when there is not enough history
+fn series_values(series: &Series, candles: &[Candle]) -> Option<Vec<f64>> {
+    if candles.is_empty() {
+        return None;
+    }
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    let values = match series {
+        Series::Open => candles.iter().map(|c| c.open).collect(),
+        Series::High => candles.iter().map(|c| c.high).collect(),
+        Series::Low => candles.iter().map(|c| c.low).collect(),
+        Series::Close => closes,
+        Series::Volume => candles.iter().map(|c| c.volume).collect(),
+        Series::Ema(n) if closes.len() >= *n => ema::calculate_ema(&closes, *n),
+        Series::Sma(n) if closes.len() >= *n => ema::calculate_sma(&closes, *n),
+        Series::Rsi(n) if closes.len() > *n => ema::calculate_rsi(&closes, *n),
+        Series::Macd if closes.len() >= 35 => ema::calculate_macd(&closes).0,
+        Series::MacdSignal if closes.len() >= 35 => ema::calculate_macd(&closes).1,
+        Series::Const(v) => vec![*v; candles.len()],
+        _ => return None,
+    };
+    Some(values)
+}
+
+// Last two values of each series for the given timeframe
+fn last_two(a: &Series, b: &Series, tf: &Option<String>, candles: &HashMap<String, Vec<Candle>>) -> Option<((f64, f64), (f64, f64))> {
+    let tf_candles = candles.get(tf.as_deref()?)?;
+    let a = series_values(a, tf_candles)?;
+    let b = series_values(b, tf_candles)?;
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    Some(((a[a.len() - 2], a[a.len() - 1]), (b[b.len() - 2], b[b.len() - 1])))
+}
+
+pub fn evaluate(cond: &Condition, candles: &HashMap<String, Vec<Candle>>) -> bool {
+    match cond {
+        Condition::CrossesAbove(a, b, tf) => match last_two(a, b, tf, candles) {
+            Some(((pa, la), (pb, lb))) => pa < pb && la > lb,
+            None => false,
+        },
+        Condition::CrossesBelow(a, b, tf) => match last_two(a, b, tf, candles) {
+            Some(((pa, la), (pb, lb))) => pa > pb && la < lb,
+            None => false,
+        },
+        Condition::Compare(a, op, b, tf) => {
+            let tf_candles = match tf.as_deref().and_then(|t| candles.get(t)) {
+                Some(c) => c,
+                None => return false,
+            };
+            let (a, b) = match (series_values(a, tf_candles), series_values(b, tf_candles)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return false,
+            };
+            let (a, b) = match (a.last(), b.last()) {
+                (Some(a), Some(b)) => (*a, *b),
+                _ => return false,
+            };
+            match op {
+                CmpOp::Lt => a < b,
+                CmpOp::Le => a <= b,
+                CmpOp::Gt => a > b,
+                CmpOp::Ge => a >= b,
+            }
+        }
+        Condition::And(a, b) => evaluate(a, candles) && evaluate(b, candles),
+        Condition::Or(a, b) => evaluate(a, candles) || evaluate(b, candles),
+        Condition::Not(c) => !evaluate(c, candles),
+    }
+}
+
+// ---- Parsing ----
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    LParen,
+    RParen,
+    Comma,
+    Op(CmpOp),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\n' | '\r' => i += 1,
+            '(' => { tokens.push(Token::LParen); i += 1; }
+            ')' => { tokens.push(Token::RParen); i += 1; }
+            ',' => { tokens.push(Token::Comma); i += 1; }
+            '<' | '>' => {
+                let eq = chars.get(i + 1) == Some(&'=');
+                let op = match (c, eq) {
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true) => CmpOp::Le,
+                    ('>', false) => CmpOp::Gt,
+                    _ => CmpOp::Ge,
+                };
+                tokens.push(Token::Op(op));
+                i += if eq { 2 } else { 1 };
+            }
+            _ if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                // "1h", "15m" are timeframes, plain numbers are constants
+                match text.parse::<f64>() {
+                    Ok(n) => tokens.push(Token::Number(n)),
+                    Err(_) => tokens.push(Token::Ident(text)),
+                }
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect::<String>().to_lowercase()));
+            }
+            _ => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref t) if *t == expected => Ok(()),
+            other => Err(format!("expected {:?}, found {:?}", expected, other)),
+        }
+    }
+
+    fn peek_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s == kw)
+    }
+
+    fn parse_or(&mut self) -> Result<Condition, String> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Condition::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // The first "on <tf>" in the chain becomes the default of its other terms
+    fn parse_and(&mut self) -> Result<Condition, String> {
+        let (first, mut chain_tf) = self.parse_unary()?;
+        let mut terms = vec![first];
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            let (term, tf) = self.parse_unary()?;
+            chain_tf = chain_tf.or(tf);
+            terms.push(term);
+        }
+        if let Some(tf) = &chain_tf {
+            for term in terms.iter_mut() {
+                set_default_timeframe(term, tf);
+            }
+        }
+        let mut terms = terms.into_iter();
+        let first = terms.next().expect("at least one term");
+        Ok(terms.fold(first, |left, right| Condition::And(Box::new(left), Box::new(right))))
+    }
+
+    // A term and the timeframe of its "on <tf>" suffix, if it has one
+    fn parse_unary(&mut self) -> Result<(Condition, Option<String>), String> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            let (cond, tf) = self.parse_unary()?;
+            return Ok((Condition::Not(Box::new(cond)), tf));
+        }
+        let mut cond = self.parse_atom()?;
+        // The suffix applies to everything in the atom
+        if self.peek_keyword("on") {
+            self.pos += 1;
+            let tf = match self.next() {
+                Some(Token::Ident(tf)) if delta::timeframe_seconds(&tf).is_some() => tf,
+                other => return Err(format!("expected timeframe after 'on', found {:?}", other)),
+            };
+            set_default_timeframe(&mut cond, &tf);
+            return Ok((cond, Some(tf)));
+        }
+        Ok((cond, None))
+    }
+
+    fn parse_atom(&mut self) -> Result<Condition, String> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let cond = self.parse_or()?;
+            self.expect(Token::RParen)?;
+            return Ok(cond);
+        }
+        if self.peek_keyword("crosses_above") || self.peek_keyword("crosses_below") {
+            let above = self.peek_keyword("crosses_above");
+            self.pos += 1;
+            self.expect(Token::LParen)?;
+            let a = self.parse_series()?;
+            self.expect(Token::Comma)?;
+            let b = self.parse_series()?;
+            self.expect(Token::RParen)?;
+            return Ok(if above { Condition::CrossesAbove(a, b, None) } else { Condition::CrossesBelow(a, b, None) });
+        }
+        let a = self.parse_series()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            other => return Err(format!("expected comparison operator, found {:?}", other)),
+        };
+        let b = self.parse_series()?;
+        Ok(Condition::Compare(a, op, b, None))
+    }
+
+    fn parse_series(&mut self) -> Result<Series, String> {
+        let name = match self.next() {
+            Some(Token::Number(n)) => return Ok(Series::Const(n)),
+            Some(Token::Ident(name)) => name,
+            other => return Err(format!("expected indicator, found {:?}", other)),
+        };
+        match name.as_str() {
+            "open" => return Ok(Series::Open),
+            "high" => return Ok(Series::High),
+            "low" => return Ok(Series::Low),
+            "close" | "price" => return Ok(Series::Close),
+            "volume" => return Ok(Series::Volume),
+            _ => {}
+        }
+        self.expect(Token::LParen)?;
+        let series = match name.as_str() {
+            "macd" => Series::Macd,
+            "macd_signal" => Series::MacdSignal,
+            "ema" | "sma" | "rsi" => {
+                let period = match self.next() {
+                    Some(Token::Number(n)) if n >= 1.0 && n.fract() == 0.0 => n as usize,
+                    other => return Err(format!("{}() needs a whole period, found {:?}", name, other)),
+                };
+                match name.as_str() {
+                    "ema" => Series::Ema(period),
+                    "sma" => Series::Sma(period),
+                    _ => Series::Rsi(period),
+                }
+            }
+            _ => return Err(format!("unknown indicator '{}'", name)),
+        };
+        self.expect(Token::RParen)?;
+        Ok(series)
+    }
+}
+
+pub fn parse_condition(input: &str) -> Result<Condition, String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err("empty condition".to_string());
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let cond = parser.parse_or()?;
+    if let Some(tok) = parser.peek() {
+        return Err(format!("unexpected {:?}", tok));
+    }
+    Ok(cond)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tf(cond: &Condition) -> Option<&str> {
+        match cond {
+            Condition::CrossesAbove(_, _, t) | Condition::CrossesBelow(_, _, t) | Condition::Compare(_, _, _, t) => t.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn rule(when: &str) -> CompiledRule {
+        compile_rule(&RuleConfig { name: "test".into(), signal: "buy".into(), when: when.into(), timeframe: "5m".into(), sizing: None }).unwrap()
+    }
+
+    #[test]
+    fn on_covers_the_whole_and_chain() {
+        let r = rule("crosses_above(ema(12), ema(26)) on 1h and rsi(14) < 70");
+        match &r.condition {
+            Condition::And(a, b) => {
+                assert!(matches!(**a, Condition::CrossesAbove(Series::Ema(12), Series::Ema(26), _)));
+                assert_eq!(tf(a), Some("1h"));
+                assert!(matches!(**b, Condition::Compare(Series::Rsi(14), CmpOp::Lt, Series::Const(v), _) if v == 70.0));
+                assert_eq!(tf(b), Some("1h"));
+            }
+            other => panic!("expected and, got {:?}", other),
+        }
+        assert_eq!(r.timeframes(), vec!["1h"]);
+    }
+
+    #[test]
+    fn explicit_timeframes_are_kept() {
+        let r = rule("close > ema(50) on 4h and rsi(14) < 70 and volume > 0 on 15m");
+        let mut tfs = r.timeframes();
+        tfs.sort();
+        assert_eq!(tfs, vec!["15m", "4h"]);
+        match &r.condition {
+            Condition::And(ab, c) => {
+                assert_eq!(tf(c), Some("15m"));
+                match &**ab {
+                    Condition::And(a, b) => {
+                        assert_eq!(tf(a), Some("4h"));
+                        assert_eq!(tf(b), Some("4h"));
+                    }
+                    other => panic!("expected and, got {:?}", other),
+                }
+            }
+            other => panic!("expected and, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn or_branches_and_groups_scope_separately() {
+        let r = rule("rsi(14) < 30 on 1h or rsi(14) < 20");
+        match &r.condition {
+            Condition::Or(a, b) => {
+                assert_eq!(tf(a), Some("1h"));
+                assert_eq!(tf(b), Some("5m"));
+            }
+            other => panic!("expected or, got {:?}", other),
+        }
+        let r = rule("not (close > sma(20) or close > ema(20)) on 1d and rsi(2) < 10");
+        let mut tfs = r.timeframes();
+        tfs.sort();
+        assert_eq!(tfs, vec!["1d"]);
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        assert!(parse_condition("").is_err());
+        assert!(parse_condition("rsi(14) <").is_err());
+        assert!(parse_condition("rsi(1.5) < 30").is_err());
+        assert!(parse_condition("foo(3) > 1").is_err());
+        assert!(parse_condition("close > 1 on 7x").is_err());
+        assert!(parse_condition("close > 1 close").is_err());
+        let bad_signal = RuleConfig { name: "x".into(), signal: "hold".into(), when: "close > 1".into(), timeframe: "5m".into(), sizing: None };
+        assert!(compile_rule(&bad_signal).is_err());
+    }
+
+    #[test]
+    fn evaluates_crosses_on_the_bound_timeframe() {
+        let candle = |close: f64| Candle { open: close, high: close, low: close, close, volume: 1.0, timestamp: 0 };
+        let mut candles = HashMap::new();
+        candles.insert("1h".to_string(), vec![candle(10.0), candle(12.0)]);
+        candles.insert("5m".to_string(), vec![candle(12.0), candle(10.0)]);
+        let r = rule("crosses_above(close, 11) on 1h and close > 11");
+        assert!(r.matches(&candles));
+        let r = rule("crosses_above(close, 11) on 1h or crosses_below(close, 11)");
+        assert!(r.matches(&candles));
+        let r = rule("crosses_below(close, 11)");
+        assert!(r.matches(&candles));
+        let r = rule("crosses_below(close, 11) on 1h");
+        assert!(!r.matches(&candles));
+    }
+
+    #[test]
+    fn rules_carry_their_sizing_override() {
+        let cfg: RuleConfig = toml::from_str(
+            "name = \"x\"\nsignal = \"buy\"\nwhen = \"close > 1\"\n[sizing]\nmodel = \"fixed_fractional\"\nrisk_pct = 0.5\n",
+        ).unwrap();
+        let sizing = compile_rule(&cfg).unwrap().sizing.unwrap();
+        assert_eq!((sizing.model.as_str(), sizing.risk_pct, sizing.atr_period), ("fixed_fractional", 0.5, 14));
+        assert!(rule("close > 1").sizing.is_none());
+    }
+
+    #[test]
+    fn rules_wait_for_their_own_timeframe_to_close() {
+        let candle = |close: f64| Candle { open: close, high: close, low: close, close, volume: 1.0, timestamp: 0 };
+        let mut candles = HashMap::new();
+        candles.insert("1h".to_string(), vec![candle(10.0), candle(12.0)]);
+        let rules = vec![rule("crosses_above(close, 11) on 1h")];
+        assert_eq!(evaluate_rules(&rules, &candles, None).map(|(_, tf)| tf).as_deref(), Some("1h"));
+        assert_eq!(evaluate_rules(&rules, &candles, Some(&["1h".to_string()])).map(|(_, tf)| tf).as_deref(), Some("1h"));
+        assert!(evaluate_rules(&rules, &candles, Some(&["5m".to_string()])).is_none());
+    }
+}
@@ -0,0 +1,374 @@
+// Weighted signal scoring
+//
+// Every component reports how much it supports a buy and a sell, each in [-1, 1].
+// The score for a direction is the weighted sum normalized to 0-100, and the
+// per-component breakdown is kept for the dashboard and Telegram.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::debounce::{self, DebounceConfig};
+use crate::delta::Candle;
+use crate::divergence::{self, DivergenceConfig};
+use crate::ema::{self, IchimokuConfig, MacdConfig};
+use crate::levels::{self, LevelConfig};
+use crate::patterns::{self, PatternConfig};
+use crate::profile::ProfileConfig;
+use crate::ranking::RankingConfig;
+use crate::regime::{self, Regime, RegimeConfig, RegimeReading};
+use crate::volume::{self, VolumeConfig};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct ScoringConfig {
+    // Timeframes that vote with their EMA crossover; the first one is the
+    // primary timeframe for MACD, volume, RSI and the other single-timeframe components
+    pub timeframes: Vec<String>,
+    // Crossovers in the same direction needed before a signal is considered
+    pub min_votes: usize,
+    // Minimum normalized score (0-100)
+    pub min_score: f64,
+    // Overrides for the default component weights, e.g. trend_1h = 30
+    pub weights: BTreeMap<String, f64>,
+    pub divergence: DivergenceConfig,
+    pub patterns: PatternConfig,
+    pub levels: LevelConfig,
+    pub regime: RegimeConfig,
+    // Regimes (of the primary timeframe) in which no signal is emitted
+    pub suppress_regimes: Vec<Regime>,
+    // Per-regime weight multipliers, e.g. [scoring.regime_weights.ranging] trend_5m = 0.5
+    pub regime_weights: HashMap<Regime, BTreeMap<String, f64>>,
+    pub debounce: DebounceConfig,
+    // Also evaluate forming candles and emit their extra signals as provisional
+    pub intrabar_preview: bool,
+    pub ranking: RankingConfig,
+    pub volume: VolumeConfig,
+    pub profile: ProfileConfig,
+    pub ichimoku: IchimokuConfig,
+    pub macd: MacdConfig,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            timeframes: ["5m", "15m", "1h", "4h", "1d"].iter().map(|s| s.to_string()).collect(),
+            min_votes: 2,
+            min_score: 25.0,
+            weights: BTreeMap::new(),
+            divergence: DivergenceConfig::default(),
+            patterns: PatternConfig::default(),
+            levels: LevelConfig::default(),
+            regime: RegimeConfig::default(),
+            suppress_regimes: Vec::new(),
+            regime_weights: HashMap::new(),
+            debounce: DebounceConfig::default(),
+            intrabar_preview: false,
+            ranking: RankingConfig::default(),
+            volume: VolumeConfig::default(),
+            profile: ProfileConfig::default(),
+            ichimoku: IchimokuConfig::default(),
+            macd: MacdConfig::default(),
+        }
+    }
+}
+
+// Scores are normalized by the total weight, so every default weight raises
+// the bar for the others: only the core trend, MACD, volume and RSI components
+// and the room to the nearest level weigh in by default; the rest are opt-in
+// with weight 0.
+fn default_weights(timeframes: &[String]) -> BTreeMap<String, f64> {
+    let mut w = BTreeMap::new();
+    for tf in timeframes {
+        w.insert(format!("trend_{}", tf), 20.0);
+        for kind in ["tk_cross", "kumo_breakout", "kumo_twist"] {
+            w.insert(format!("{}_{}", kind, tf), 0.0);
+        }
+    }
+    w.insert("macd".to_string(), 20.0);
+    w.insert("volume_spike".to_string(), 10.0);
+    w.insert("rvol".to_string(), 10.0);
+    w.insert("rsi".to_string(), 10.0);
+    // Downgrades signals that run straight into a level
+    w.insert("levels".to_string(), 10.0);
+    for name in ["divergence_rsi", "divergence_macd", "pattern", "regime", "relative_strength", "value_area"] {
+        w.insert(name.to_string(), 0.0);
+    }
+    w
+}
+
+// Raw component reading before weighting
+#[derive(Clone, Debug)]
+pub struct Component {
+    pub name: String,
+    pub bull: f64,
+    pub bear: f64,
+}
+
+impl Component {
+    pub fn directional(name: &str, signal: Option<&str>) -> Self {
+        let (bull, bear) = match signal {
+            Some("buy") => (1.0, 0.0),
+            Some("sell") => (0.0, 1.0),
+            _ => (0.0, 0.0),
+        };
+        Self { name: name.to_string(), bull, bear }
+    }
+
+    pub fn neutral(name: &str, value: f64) -> Self {
+        let v = value.clamp(-1.0, 1.0);
+        Self { name: name.to_string(), bull: v, bear: v }
+    }
+
+    pub fn value(&self, direction: &str) -> f64 {
+        if direction == "buy" { self.bull } else { self.bear }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ComponentScore {
+    pub name: String,
+    pub weight: f64,
+    pub value: f64,
+    // Contribution to the 0-100 score
+    pub points: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Score {
+    pub direction: &'static str,
+    pub score: f64,
+    pub regime: Option<Regime>,
+    pub breakdown: Vec<ComponentScore>,
+}
+
+pub struct ScoringEngine {
+    pub config: ScoringConfig,
+    weights: BTreeMap<String, f64>,
+}
+
+impl ScoringEngine {
+    pub fn new(config: ScoringConfig) -> Self {
+        let mut weights = default_weights(&config.timeframes);
+        weights.extend(config.weights.clone());
+        Self { config, weights }
+    }
+
+    // Timeframes components() expects candles for
+    pub fn required_timeframes(&self) -> Vec<String> {
+        let mut tfs = self.config.timeframes.clone();
+        let extra = [
+            self.config.levels.timeframe.as_str(),
+            self.config.ranking.timeframe.as_str(),
+            self.config.profile.timeframe.as_str(),
+            "1d",
+        ];
+        for tf in extra.into_iter().chain(self.config.volume.alert_timeframes.iter().map(|t| t.as_str())) {
+            if !tfs.iter().any(|t| t == tf) {
+                tfs.push(tf.to_string());
+            }
+        }
+        tfs
+    }
+
+    // Component weight after the regime multiplier, if any
+    pub fn weight(&self, name: &str, regime: Option<Regime>) -> f64 {
+        let base = self.weights.get(name).cloned().unwrap_or(0.0);
+        let mult = regime
+            .and_then(|r| self.config.regime_weights.get(&r))
+            .and_then(|m| m.get(name))
+            .cloned()
+            .unwrap_or(1.0);
+        base * mult
+    }
+
+    // Timeframe of a per-timeframe vote such as trend_1h or tk_cross_4h
+    pub fn component_timeframe<'a>(&self, name: &'a str) -> Option<&'a str> {
+        ["trend_", "tk_cross_", "kumo_breakout_", "kumo_twist_"].iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .filter(|tf| self.config.timeframes.iter().any(|t| t == tf))
+    }
+
+    // Regime of the primary timeframe
+    pub fn regime(&self, candles: &HashMap<String, Vec<Candle>>) -> Option<RegimeReading> {
+        let primary = candles.get(self.config.timeframes.first()?)?;
+        regime::classify(primary, &self.config.regime)
+    }
+
+    // Regime of every scoring timeframe, for the dashboard
+    pub fn regimes(&self, candles: &HashMap<String, Vec<Candle>>) -> BTreeMap<String, RegimeReading> {
+        self.config.timeframes.iter()
+            .filter_map(|tf| Some((tf.clone(), regime::classify(candles.get(tf)?, &self.config.regime)?)))
+            .collect()
+    }
+
+    // Built-in components computed from per-timeframe candles (closed ones,
+    // plus the forming candle in preview mode)
+    pub fn components(&self, candles: &HashMap<String, Vec<Candle>>) -> Vec<Component> {
+        let mut components = Vec::new();
+        for tf in &self.config.timeframes {
+            let tf_candles = candles.get(tf).map(|c| c.as_slice()).unwrap_or(&[]);
+            let closes: Vec<f64> = tf_candles.iter().map(|c| c.close).collect();
+            let mut crossover = debounce::confirmed_crossover(&closes, &self.config.debounce);
+            if self.config.patterns.confirm_crossovers {
+                crossover = patterns::confirm_crossover(crossover, tf_candles, &self.config.patterns);
+            }
+            components.push(Component::directional(&format!("trend_{}", tf), crossover));
+
+            let highs: Vec<f64> = tf_candles.iter().map(|c| c.high).collect();
+            let lows: Vec<f64> = tf_candles.iter().map(|c| c.low).collect();
+            // The cloud is computed once and shared by the three Ichimoku signals
+            let ich = ema::ichimoku_ready(&closes, &self.config.ichimoku)
+                .then(|| ema::calculate_ichimoku(&highs, &lows, &closes, &self.config.ichimoku));
+            let ich = ich.as_ref();
+            components.push(Component::directional(&format!("tk_cross_{}", tf), ich.and_then(ema::detect_tk_cross)));
+            components.push(Component::directional(&format!("kumo_breakout_{}", tf), ich.and_then(|i| ema::detect_kumo_breakout(&closes, i))));
+            components.push(Component::directional(&format!("kumo_twist_{}", tf), ich.and_then(ema::detect_kumo_twist)));
+        }
+        let primary = self.config.timeframes.first()
+            .and_then(|tf| candles.get(tf))
+            .map(|c| c.as_slice())
+            .unwrap_or(&[]);
+        let closes: Vec<f64> = primary.iter().map(|c| c.close).collect();
+        components.push(Component::directional("macd", ema::detect_macd_crossover(&closes, &self.config.macd)));
+
+        // Unusual volume backs either direction: the spike score as a fraction of
+        // the threshold, and RVOL from 1x (0) up to the RVOL threshold (1)
+        if let Some(v) = volume::analyze(primary, &self.config.volume) {
+            components.push(Component::neutral("volume_spike", (v.score / self.config.volume.spike_threshold).max(0.0)));
+            if let Some(rvol) = v.rvol {
+                let span = (self.config.volume.rvol_threshold - 1.0).max(f64::EPSILON);
+                components.push(Component::neutral("rvol", (rvol - 1.0) / span));
+            }
+        }
+
+        if closes.len() > 14 {
+            let rsi = ema::calculate_rsi(&closes, 14).last().cloned().unwrap_or(50.0);
+            let momentum = ((rsi - 50.0) / 20.0).clamp(-1.0, 1.0);
+            components.push(Component { name: "rsi".to_string(), bull: momentum, bear: -momentum });
+        }
+
+        components.push(Component::directional("pattern", patterns::pattern_signal(primary, &self.config.patterns)));
+
+        // Divergences on the primary timeframe
+        if closes.len() >= 35 {
+            let cfg = &self.config.divergence;
+            let rsi_div = divergence::latest_divergence(primary, &ema::calculate_rsi(&closes, 14), cfg);
+            components.push(Component::directional("divergence_rsi", rsi_div.map(|d| d.kind.signal())));
+            let macd_div = divergence::latest_divergence(primary, &ema::calculate_macd_histogram(&closes, &self.config.macd), cfg);
+            components.push(Component::directional("divergence_macd", macd_div.map(|d| d.kind.signal())));
+        }
+
+        // Room to the nearest level in the signal's direction: -1 right at the
+        // level, 0 at near_pct away, +1 at twice that or with no level in the way
+        if let Some(lv) = levels::compute_levels(candles, &self.config.levels) {
+            let near = self.config.levels.near_pct.max(f64::EPSILON);
+            let room = |d: Option<f64>| d.map_or(1.0, |d| (d / near - 1.0).clamp(-1.0, 1.0));
+            components.push(Component {
+                name: "levels".to_string(),
+                bull: room(lv.distance_to_resistance()),
+                bear: room(lv.distance_to_support()),
+            });
+        }
+
+        // Agreement with the primary timeframe's trend regime
+        if let Some(reading) = self.regime(candles) {
+            let trend = match reading.regime {
+                Regime::TrendingUp => 1.0,
+                Regime::TrendingDown => -1.0,
+                _ => 0.0,
+            };
+            components.push(Component { name: "regime".to_string(), bull: trend, bear: -trend });
+        }
+        components
+    }
+
+    // Direction from timeframe crossover votes, falling back to a candlestick
+    // pattern when patterns are configured as standalone signals
+    pub fn vote(&self, components: &[Component]) -> Option<&'static str> {
+        let trend = components.iter().filter(|c| c.name.starts_with("trend_"));
+        let buys = trend.clone().filter(|c| c.bull >= 1.0).count();
+        let sells = trend.filter(|c| c.bear >= 1.0).count();
+        if buys >= self.config.min_votes {
+            Some("buy")
+        } else if sells >= self.config.min_votes {
+            Some("sell")
+        } else if self.config.patterns.standalone {
+            let pattern = components.iter().find(|c| c.name == "pattern")?;
+            if pattern.bull >= 1.0 {
+                Some("buy")
+            } else if pattern.bear >= 1.0 {
+                Some("sell")
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    pub fn score(&self, components: &[Component], direction: &'static str, regime: Option<Regime>) -> Score {
+        let total: f64 = components.iter().map(|c| self.weight(&c.name, regime).max(0.0)).sum();
+        let breakdown: Vec<ComponentScore> = components.iter().map(|c| {
+            let weight = self.weight(&c.name, regime);
+            let value = c.value(direction);
+            let points = if total > 0.0 { weight * value / total * 100.0 } else { 0.0 };
+            ComponentScore { name: c.name.clone(), weight, value, points }
+        }).collect();
+        let score = breakdown.iter().map(|c| c.points).sum::<f64>().clamp(0.0, 100.0);
+        Score { direction, score, regime, breakdown }
+    }
+
+    // Scores the voted direction, or `direction` when a rule already chose one
+    pub fn evaluate(&self, components: &[Component], direction: Option<&'static str>, regime: Option<Regime>) -> Option<Score> {
+        if regime.is_some_and(|r| self.config.suppress_regimes.contains(&r)) {
+            return None;
+        }
+        let direction = direction.or_else(|| self.vote(components))?;
+        let score = self.score(components, direction, regime);
+        if score.score >= self.config.min_score { Some(score) } else { None }
+    }
+}
+
+// Short summary like "trend_1h +20, macd +20" for Telegram
+pub fn format_breakdown(breakdown: &[ComponentScore]) -> String {
+    breakdown.iter()
+        .filter(|c| c.points.abs() >= 0.5)
+        .map(|c| format!("{} {:+.0}", c.name, c.points))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trend_votes(n: usize, timeframes: &[String]) -> Vec<Component> {
+        let mut components: Vec<Component> = timeframes.iter().enumerate()
+            .map(|(i, tf)| Component::directional(&format!("trend_{}", tf), (i < n).then_some("buy")))
+            .collect();
+        for name in ["macd", "volume_spike", "rvol", "rsi", "divergence_rsi", "divergence_macd", "pattern", "levels", "regime", "relative_strength", "value_area"] {
+            components.push(Component::neutral(name, 0.0));
+        }
+        components
+    }
+
+    #[test]
+    fn two_trend_votes_pass_the_default_min_score() {
+        let engine = ScoringEngine::new(ScoringConfig::default());
+        let components = trend_votes(2, &engine.config.timeframes);
+        let score = engine.evaluate(&components, None, None).expect("signal");
+        assert_eq!(score.direction, "buy");
+        // 2 x 20 of the 160 default weight
+        assert!((score.score - 40.0 / 160.0 * 100.0).abs() < 1e-9);
+        assert!(engine.evaluate(&trend_votes(1, &engine.config.timeframes), None, None).is_none());
+    }
+
+    #[test]
+    fn opt_in_components_share_the_total() {
+        let mut cfg = ScoringConfig::default();
+        cfg.weights.insert("pattern".to_string(), 40.0);
+        let engine = ScoringEngine::new(cfg);
+        let score = engine.score(&trend_votes(2, &engine.config.timeframes), "buy", None);
+        assert!((score.score - 40.0 / 200.0 * 100.0).abs() < 1e-9);
+        let pattern = score.breakdown.iter().find(|c| c.name == "pattern").unwrap();
+        assert_eq!(pattern.weight, 40.0);
+    }
+}
@@ -0,0 +1,206 @@
+// Sandboxed Rhai strategy scripts
+//
+// A script runs once per symbol per cycle with these variables in scope:
+//   symbol      - e.g. "BTCUSD"
+//   candles     - map of timeframe -> array of #{open, high, low, close, volume, timestamp}
+//   indicators  - map of timeframe -> #{ema12, ema26, rsi14, macd, macd_signal} (latest values)
+//   state       - per-symbol map that is kept between runs
+// and ema(arr, n), sma(arr, n), rsi(arr, n), closes(arr) available as functions.
+// It returns () for no signal, or #{signal: "buy"|"sell", score: 0..100, timeframe: "1h"}
+// (or an array of those).
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{error, warn};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+use crate::delta::Candle;
+use crate::ema;
+use crate::sizing::SizingConfig;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct ScriptConfig {
+    pub name: String,
+    pub path: String,
+    // Wall-clock limit for one run
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+    // Caps on string length and array/map entries, which bound memory use
+    #[serde(default = "default_max_collection_size")]
+    pub max_collection_size: usize,
+    // Consecutive failed runs before the script is disabled
+    #[serde(default = "default_max_failures")]
+    pub max_failures: u32,
+    // Sizing for this script's signals instead of the engine's own
+    #[serde(default)]
+    pub sizing: Option<SizingConfig>,
+}
+
+fn default_timeout_ms() -> u64 { 50 }
+fn default_max_operations() -> u64 { 1_000_000 }
+fn default_max_collection_size() -> usize { 100_000 }
+fn default_max_failures() -> u32 { 5 }
+
+#[derive(Clone, Debug)]
+pub struct ScriptSignal {
+    pub script: String,
+    pub signal: &'static str,
+    pub score: f64,
+    pub timeframe: String,
+}
+
+pub struct StrategyScript {
+    pub config: ScriptConfig,
+    engine: Engine,
+    ast: AST,
+    deadline: Arc<Mutex<Option<Instant>>>,
+    state: HashMap<String, Map>,
+    failures: u32,
+}
+
+impl StrategyScript {
+    pub fn load(config: ScriptConfig) -> Result<Self, String> {
+        let source = std::fs::read_to_string(&config.path).map_err(|e| format!("{}: {}", config.path, e))?;
+        let deadline: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+        let mut engine = Engine::new();
+        engine.set_max_operations(config.max_operations);
+        engine.set_max_string_size(config.max_collection_size);
+        engine.set_max_array_size(config.max_collection_size);
+        engine.set_max_map_size(config.max_collection_size);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_modules(0);
+        let progress_deadline = deadline.clone();
+        engine.on_progress(move |_ops| {
+            match *progress_deadline.lock().unwrap() {
+                Some(d) if Instant::now() > d => Some("timeout".into()),
+                _ => None,
+            }
+        });
+        engine.register_fn("ema", |arr: Array, period: i64| to_array(&ema::calculate_ema(&from_array(&arr), period.max(1) as usize)));
+        engine.register_fn("sma", |arr: Array, period: i64| to_array(&ema::calculate_sma(&from_array(&arr), period.max(1) as usize)));
+        engine.register_fn("rsi", |arr: Array, period: i64| to_array(&ema::calculate_rsi(&from_array(&arr), period.max(1) as usize)));
+        engine.register_fn("closes", |candles: Array| {
+            candles.iter()
+                .filter_map(|c| c.read_lock::<Map>().and_then(|m| m.get("close").and_then(|v| v.as_float().ok())))
+                .map(Dynamic::from_float)
+                .collect::<Array>()
+        });
+        let ast = engine.compile(&source).map_err(|e| format!("{}: {}", config.path, e))?;
+        Ok(Self { config, engine, ast, deadline, state: HashMap::new(), failures: 0 })
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.failures >= self.config.max_failures
+    }
+
+    // Runs the script for one symbol; errors and timeouts count towards disabling it
+    pub fn evaluate(&mut self, symbol: &str, candles: &HashMap<String, Vec<Candle>>) -> Vec<ScriptSignal> {
+        if self.is_disabled() {
+            return Vec::new();
+        }
+        let mut scope = Scope::new();
+        scope.push("symbol", symbol.to_string());
+        scope.push("candles", candles_to_map(candles));
+        scope.push("indicators", indicators_to_map(candles));
+        scope.push("state", self.state.get(symbol).cloned().unwrap_or_default());
+
+        *self.deadline.lock().unwrap() = Some(Instant::now() + Duration::from_millis(self.config.timeout_ms));
+        let result = self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast);
+        *self.deadline.lock().unwrap() = None;
+
+        let output = match result {
+            Ok(v) => v,
+            Err(e) => {
+                self.failures += 1;
+                warn!("Script {} failed on {}: {}", self.config.name, symbol, e);
+                if self.is_disabled() {
+                    error!("Script {} disabled after {} consecutive failures", self.config.name, self.failures);
+                }
+                return Vec::new();
+            }
+        };
+        self.failures = 0;
+        if let Some(state) = scope.get_value::<Map>("state") {
+            self.state.insert(symbol.to_string(), state);
+        }
+        let items: Vec<Dynamic> = if output.is_array() {
+            output.cast::<Array>()
+        } else {
+            vec![output]
+        };
+        items.into_iter().filter_map(|item| self.parse_signal(item)).collect()
+    }
+
+    fn parse_signal(&self, item: Dynamic) -> Option<ScriptSignal> {
+        let map = item.try_cast::<Map>()?;
+        let signal = match map.get("signal")?.clone().into_string().ok()?.as_str() {
+            "buy" => "buy",
+            "sell" => "sell",
+            _ => return None,
+        };
+        let score = map.get("score")
+            .and_then(|v| v.as_float().ok().or_else(|| v.as_int().ok().map(|i| i as f64)))
+            .unwrap_or(0.0)
+            .clamp(0.0, 100.0);
+        let timeframe = map.get("timeframe")
+            .and_then(|v| v.clone().into_string().ok())
+            .unwrap_or_else(|| "5m".to_string());
+        Some(ScriptSignal { script: self.config.name.clone(), signal, score, timeframe })
+    }
+}
+
+pub fn load_scripts(configs: &[ScriptConfig]) -> Result<Vec<StrategyScript>, String> {
+    configs.iter().cloned().map(StrategyScript::load).collect()
+}
+
+fn from_array(arr: &Array) -> Vec<f64> {
+    arr.iter()
+        .filter_map(|v| v.as_float().ok().or_else(|| v.as_int().ok().map(|i| i as f64)))
+        .collect()
+}
+
+fn to_array(values: &[f64]) -> Array {
+    values.iter().map(|&v| Dynamic::from_float(v)).collect()
+}
+
+fn candles_to_map(candles: &HashMap<String, Vec<Candle>>) -> Map {
+    let mut out = Map::new();
+    for (tf, series) in candles {
+        let arr: Array = series.iter().map(|c| {
+            let mut m = Map::new();
+            m.insert("open".into(), Dynamic::from_float(c.open));
+            m.insert("high".into(), Dynamic::from_float(c.high));
+            m.insert("low".into(), Dynamic::from_float(c.low));
+            m.insert("close".into(), Dynamic::from_float(c.close));
+            m.insert("volume".into(), Dynamic::from_float(c.volume));
+            m.insert("timestamp".into(), Dynamic::from_int(c.timestamp as i64));
+            Dynamic::from_map(m)
+        }).collect();
+        out.insert(tf.as_str().into(), Dynamic::from_array(arr));
+    }
+    out
+}
+
+fn indicators_to_map(candles: &HashMap<String, Vec<Candle>>) -> Map {
+    let mut out = Map::new();
+    for (tf, series) in candles {
+        if series.is_empty() {
+            continue;
+        }
+        let closes: Vec<f64> = series.iter().map(|c| c.close).collect();
+        let (macd, signal) = ema::calculate_macd(&closes);
+        let last = |v: &[f64]| Dynamic::from_float(v.last().cloned().unwrap_or(0.0));
+        let mut m = Map::new();
+        m.insert("ema12".into(), last(&ema::calculate_ema(&closes, 12)));
+        m.insert("ema26".into(), last(&ema::calculate_ema(&closes, 26)));
+        m.insert("rsi14".into(), last(&ema::calculate_rsi(&closes, 14)));
+        m.insert("macd".into(), last(&macd));
+        m.insert("macd_signal".into(), last(&signal));
+        out.insert(tf.as_str().into(), Dynamic::from_map(m));
+    }
+    out
+}
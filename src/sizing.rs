@@ -0,0 +1,200 @@
+// Position sizing models
+//
+//   fixed_notional   - a constant notional per entry
+//   equity_pct       - equity_pct of equity per entry
+//   fixed_fractional - risk risk_pct of equity between entry and stop
+//   atr              - risk risk_pct of equity over atr_multiplier ATRs
+//   kelly            - kelly_fraction of the Kelly stake for the configured
+//                      win rate and payoff ratio
+// Every model is capped at max_leverage times equity and rounded down to
+// whole contracts of the product's contract value. Live execution, paper
+// trading and backtests all size here; a rule or script can override the
+// model with its own `sizing` table, carried on the signals it raises.
+use crate::delta::Candle;
+use crate::ema;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct SizingConfig {
+    // "fixed_notional", "equity_pct", "fixed_fractional", "atr" or "kelly"
+    pub model: String,
+    pub notional: f64,
+    pub equity_pct: f64,
+    // Equity at risk per trade, in % (fixed_fractional and atr)
+    pub risk_pct: f64,
+    pub atr_period: usize,
+    // Adverse move the risk budget covers, in ATRs
+    pub atr_multiplier: f64,
+    // Win probability (0-1) and average win over average loss for Kelly
+    pub kelly_win_rate: f64,
+    pub kelly_payoff: f64,
+    // Share of the full Kelly stake to use
+    pub kelly_fraction: f64,
+    // Notional cap as a multiple of equity
+    pub max_leverage: f64,
+}
+
+impl Default for SizingConfig {
+    fn default() -> Self {
+        Self {
+            model: "fixed_notional".to_string(),
+            notional: 100.0,
+            equity_pct: 10.0,
+            risk_pct: 1.0,
+            atr_period: 14,
+            atr_multiplier: 2.0,
+            kelly_win_rate: 0.5,
+            kelly_payoff: 1.5,
+            kelly_fraction: 0.5,
+            max_leverage: 5.0,
+        }
+    }
+}
+
+// What a model may need to know about one entry
+#[derive(Clone, Debug, Default)]
+pub struct SizingInput {
+    pub equity: f64,
+    pub price: f64,
+    pub stop_price: Option<f64>,
+    pub atr: Option<f64>,
+    pub contract_value: f64,
+}
+
+// Latest ATR of closed candles, if there are enough of them
+pub fn latest_atr(candles: &[Candle], period: usize) -> Option<f64> {
+    if period == 0 || candles.len() < period {
+        return None;
+    }
+    let highs: Vec<f64> = candles.iter().map(|c| c.high).collect();
+    let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    ema::calculate_atr(&highs, &lows, &closes, period).last().copied().filter(|v| *v > 0.0)
+}
+
+// Optimal fraction of equity to stake: p - (1 - p) / b
+pub fn kelly(win_rate: f64, payoff: f64) -> f64 {
+    if payoff <= 0.0 {
+        return 0.0;
+    }
+    (win_rate - (1.0 - win_rate) / payoff).max(0.0)
+}
+
+// Notional for one entry, capped by max leverage
+pub fn notional(cfg: &SizingConfig, input: &SizingInput) -> Result<f64, String> {
+    if input.price <= 0.0 {
+        return Err("no price to size against".to_string());
+    }
+    let risk = input.equity * cfg.risk_pct / 100.0;
+    let raw = match cfg.model.as_str() {
+        "fixed_notional" => cfg.notional,
+        "equity_pct" => input.equity * cfg.equity_pct / 100.0,
+        "fixed_fractional" => {
+            let stop = input.stop_price.ok_or("fixed_fractional sizing needs a stop")?;
+            let distance = (input.price - stop).abs() / input.price;
+            if distance <= 0.0 {
+                return Err("stop is at the entry price".to_string());
+            }
+            risk / distance
+        }
+        "atr" => {
+            let atr = input.atr.ok_or("not enough candles for ATR sizing")?;
+            risk / (atr * cfg.atr_multiplier) * input.price
+        }
+        "kelly" => input.equity * kelly(cfg.kelly_win_rate, cfg.kelly_payoff) * cfg.kelly_fraction,
+        other => return Err(format!("unknown sizing model '{}'", other)),
+    };
+    let cap = if cfg.max_leverage > 0.0 { input.equity * cfg.max_leverage } else { f64::MAX };
+    Ok(raw.min(cap).max(0.0))
+}
+
+// Whole contracts for one entry; zero when the notional buys less than one
+pub fn contracts(cfg: &SizingConfig, input: &SizingInput) -> Result<u64, String> {
+    if input.contract_value <= 0.0 {
+        return Err("contract value must be positive".to_string());
+    }
+    let n = notional(cfg, input)?;
+    Ok((n / (input.price * input.contract_value)).floor() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input() -> SizingInput {
+        SizingInput { equity: 10_000.0, price: 100.0, stop_price: Some(98.0), atr: Some(1.5), contract_value: 0.1 }
+    }
+
+    fn cfg(model: &str) -> SizingConfig {
+        SizingConfig { model: model.to_string(), ..SizingConfig::default() }
+    }
+
+    #[test]
+    fn fixed_notional_rounds_down_to_contracts() {
+        let c = SizingConfig { notional: 1_005.0, ..cfg("fixed_notional") };
+        // 1005 / (100 * 0.1) = 100.5 contracts
+        assert_eq!(contracts(&c, &input()).unwrap(), 100);
+    }
+
+    #[test]
+    fn equity_pct_follows_equity() {
+        // 10% of 10k = 1000 notional -> 100 contracts
+        assert_eq!(contracts(&cfg("equity_pct"), &input()).unwrap(), 100);
+        let smaller = SizingInput { equity: 5_000.0, ..input() };
+        assert_eq!(contracts(&cfg("equity_pct"), &smaller).unwrap(), 50);
+    }
+
+    #[test]
+    fn fixed_fractional_risks_the_stop_distance() {
+        // 1% of 10k = 100 at risk over a 2% stop -> 5000 notional -> 500 contracts
+        let c = cfg("fixed_fractional");
+        assert!((notional(&c, &input()).unwrap() - 5_000.0).abs() < 1e-9);
+        assert_eq!(contracts(&c, &input()).unwrap(), 500);
+        let short = SizingInput { stop_price: Some(102.0), ..input() };
+        assert_eq!(contracts(&c, &short).unwrap(), 500);
+        let no_stop = SizingInput { stop_price: None, ..input() };
+        assert!(contracts(&c, &no_stop).is_err());
+    }
+
+    #[test]
+    fn atr_sizing_scales_with_volatility() {
+        // 100 at risk over 2 * 1.5 = 3 per unit -> 33.33 units -> 3333.33 notional
+        let c = cfg("atr");
+        assert_eq!(contracts(&c, &input()).unwrap(), 333);
+        let calmer = SizingInput { atr: Some(0.75), ..input() };
+        assert_eq!(contracts(&c, &calmer).unwrap(), 666);
+        let no_atr = SizingInput { atr: None, ..input() };
+        assert!(contracts(&c, &no_atr).is_err());
+    }
+
+    #[test]
+    fn kelly_fraction_of_equity() {
+        assert!((kelly(0.5, 1.5) - 1.0 / 6.0).abs() < 1e-9);
+        assert_eq!(kelly(0.3, 1.0), 0.0);
+        // Half Kelly: 10k * 1/6 * 0.5 = 833.33 -> 83 contracts
+        assert_eq!(contracts(&cfg("kelly"), &input()).unwrap(), 83);
+    }
+
+    #[test]
+    fn leverage_caps_every_model() {
+        let c = SizingConfig { max_leverage: 2.0, kelly_win_rate: 0.9, kelly_payoff: 10.0, kelly_fraction: 30.0, ..cfg("kelly") };
+        assert!((notional(&c, &input()).unwrap() - 20_000.0).abs() < 1e-9);
+        let tight = SizingInput { stop_price: Some(99.99), ..input() };
+        let c = SizingConfig { max_leverage: 3.0, ..cfg("fixed_fractional") };
+        assert!((notional(&c, &tight).unwrap() - 30_000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn below_one_contract_is_zero() {
+        let c = SizingConfig { notional: 5.0, ..cfg("fixed_notional") };
+        assert_eq!(contracts(&c, &input()).unwrap(), 0);
+        assert!(contracts(&cfg("martingale"), &input()).is_err());
+    }
+
+    #[test]
+    fn atr_needs_enough_candles() {
+        let candles: Vec<Candle> = (0..20).map(|i| Candle { open: 100.0, high: 101.0, low: 99.0, close: 100.0, volume: 1.0, timestamp: i * 60 }).collect();
+        assert!((latest_atr(&candles, 14).unwrap() - 2.0).abs() < 1e-9);
+        assert!(latest_atr(&candles[..5], 14).is_none());
+    }
+}
@@ -0,0 +1,414 @@
+// SQLite persistence for candles, ticks, signals and orders
+//
+// The schema is versioned with PRAGMA user_version: each entry of MIGRATIONS
+// runs once, in order, inside a transaction. Closed candles newly added to the
+// candle history (see history.rs) are written once per cycle in a single
+// transaction and seed that history on startup, so only the gap since the last
+// run is fetched. Raw ticks are optional and pruned after tick_retention_hours.
+// On startup the agent reloads the tick buffer, the last cycle's signals and
+// unfinished orders from here.
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection};
+
+use crate::delta::Candle;
+use crate::orders::{OrderState, TrackedOrder};
+use crate::regime::Regime;
+use crate::scoring::ComponentScore;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub enabled: bool,
+    pub path: String,
+    // Raw ticks grow quickly; off by default
+    pub store_ticks: bool,
+    pub tick_retention_hours: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "agent.db".to_string(),
+            store_ticks: false,
+            tick_retention_hours: 48,
+        }
+    }
+}
+
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE candles (
+        symbol TEXT NOT NULL,
+        timeframe TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        open REAL NOT NULL,
+        high REAL NOT NULL,
+        low REAL NOT NULL,
+        close REAL NOT NULL,
+        volume REAL NOT NULL,
+        PRIMARY KEY (symbol, timeframe, timestamp)
+    );
+    CREATE TABLE ticks (
+        symbol TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        price REAL NOT NULL,
+        volume REAL NOT NULL
+    );
+    CREATE INDEX ticks_symbol_time ON ticks (symbol, timestamp);
+    CREATE TABLE signals (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        time INTEGER NOT NULL,
+        symbol TEXT NOT NULL,
+        timeframe TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        score REAL NOT NULL,
+        source TEXT NOT NULL,
+        regime TEXT,
+        provisional INTEGER NOT NULL,
+        price REAL NOT NULL,
+        volume REAL NOT NULL,
+        breakdown TEXT NOT NULL
+    );
+    CREATE INDEX signals_time ON signals (time);
+    CREATE TABLE orders (
+        client_order_id TEXT PRIMARY KEY,
+        exchange_id INTEGER,
+        symbol TEXT NOT NULL,
+        product_id INTEGER NOT NULL,
+        side TEXT NOT NULL,
+        role TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        filled INTEGER NOT NULL,
+        avg_fill_price REAL NOT NULL,
+        state TEXT NOT NULL,
+        reason TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX orders_state ON orders (state);",
+];
+
+// One emitted signal as stored
+#[derive(Clone, Debug)]
+pub struct StoredSignal {
+    pub time: u64,
+    pub symbol: String,
+    pub timeframe: String,
+    pub direction: String,
+    pub score: f64,
+    pub source: String,
+    pub regime: Option<Regime>,
+    pub provisional: bool,
+    pub price: f64,
+    pub volume: f64,
+    pub breakdown: Vec<ComponentScore>,
+}
+
+// (price, volume, timestamp) ticks per symbol, as kept in memory
+pub type Ticks = HashMap<String, Vec<(f64, f64, u64)>>;
+
+fn db_err(e: rusqlite::Error) -> String {
+    format!("database: {}", e)
+}
+
+// Enum values go through their serde names so the text matches the JSON API
+fn to_text<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_value(value).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+fn from_text<T: serde::de::DeserializeOwned>(text: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(text.to_string())).ok()
+}
+
+pub struct Storage {
+    conn: Connection,
+}
+
+impl Storage {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(db_err)?;
+        let mut storage = Self { conn };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    // Applies the migrations newer than the database's user_version
+    fn migrate(&mut self) -> Result<(), String> {
+        let version: usize = self.conn.pragma_query_value(None, "user_version", |r| r.get(0)).map_err(db_err)?;
+        for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction().map_err(db_err)?;
+            tx.execute_batch(sql).map_err(|e| format!("migration {}: {}", i + 1, e))?;
+            tx.pragma_update(None, "user_version", i + 1).map_err(db_err)?;
+            tx.commit().map_err(db_err)?;
+        }
+        Ok(())
+    }
+
+    // Upserts (symbol, timeframe, candles) batches in one transaction
+    pub fn save_candles(&mut self, batches: &[(String, String, Vec<Candle>)]) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(db_err)?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO candles (symbol, timeframe, timestamp, open, high, low, close, volume)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            ).map_err(db_err)?;
+            for (symbol, timeframe, candles) in batches {
+                for c in candles {
+                    stmt.execute(params![symbol, timeframe, c.timestamp as i64, c.open, c.high, c.low, c.close, c.volume]).map_err(db_err)?;
+                }
+            }
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    // The last `limit` candles opening before `before`, oldest first
+    pub fn load_candles(&self, symbol: &str, timeframe: &str, before: u64, limit: usize) -> Result<Vec<Candle>, String> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT timestamp, open, high, low, close, volume FROM candles
+             WHERE symbol = ?1 AND timeframe = ?2 AND timestamp < ?3
+             ORDER BY timestamp DESC LIMIT ?4",
+        ).map_err(db_err)?;
+        let rows = stmt.query_map(params![symbol, timeframe, before.min(i64::MAX as u64) as i64, limit as i64], |r| {
+            Ok(Candle {
+                timestamp: r.get::<_, i64>(0)? as u64,
+                open: r.get(1)?,
+                high: r.get(2)?,
+                low: r.get(3)?,
+                close: r.get(4)?,
+                volume: r.get(5)?,
+            })
+        }).map_err(db_err)?;
+        let mut candles = rows.collect::<Result<Vec<_>, _>>().map_err(db_err)?;
+        candles.reverse();
+        Ok(candles)
+    }
+
+    // Appends (symbol, price, volume, timestamp) ticks
+    pub fn save_ticks(&mut self, ticks: &[(String, f64, f64, u64)]) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(db_err)?;
+        {
+            let mut stmt = tx.prepare_cached("INSERT INTO ticks (symbol, timestamp, price, volume) VALUES (?1, ?2, ?3, ?4)").map_err(db_err)?;
+            for (symbol, price, volume, ts) in ticks {
+                stmt.execute(params![symbol, *ts as i64, price, volume]).map_err(db_err)?;
+            }
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    pub fn prune_ticks(&self, before: u64) -> Result<usize, String> {
+        self.conn.execute("DELETE FROM ticks WHERE timestamp < ?1", params![before as i64]).map_err(db_err)
+    }
+
+    // The last `per_symbol` ticks of every symbol as (price, volume, timestamp), oldest first
+    pub fn load_ticks(&self, per_symbol: usize) -> Result<Ticks, String> {
+        let mut stmt = self.conn.prepare(
+            "SELECT symbol, price, volume, timestamp FROM (
+                SELECT symbol, price, volume, timestamp, rowid,
+                       ROW_NUMBER() OVER (PARTITION BY symbol ORDER BY timestamp DESC, rowid DESC) AS n
+                FROM ticks)
+             WHERE n <= ?1 ORDER BY symbol, timestamp, rowid",
+        ).map_err(db_err)?;
+        let rows = stmt.query_map(params![per_symbol as i64], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, f64>(1)?, r.get::<_, f64>(2)?, r.get::<_, i64>(3)? as u64))
+        }).map_err(db_err)?;
+        let mut ticks = Ticks::new();
+        for row in rows {
+            let (symbol, price, volume, ts) = row.map_err(db_err)?;
+            ticks.entry(symbol).or_default().push((price, volume, ts));
+        }
+        Ok(ticks)
+    }
+
+    pub fn save_signal(&self, s: &StoredSignal) -> Result<(), String> {
+        let breakdown = serde_json::to_string(&s.breakdown).map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "INSERT INTO signals (time, symbol, timeframe, direction, score, source, regime, provisional, price, volume, breakdown)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![s.time as i64, s.symbol, s.timeframe, s.direction, s.score, s.source, s.regime.as_ref().map(to_text), s.provisional, s.price, s.volume, breakdown],
+        ).map_err(db_err)?;
+        Ok(())
+    }
+
+    // Signals of the most recent cycle that emitted any
+    pub fn last_signals(&self) -> Result<Vec<StoredSignal>, String> {
+        let mut stmt = self.conn.prepare(
+            "SELECT time, symbol, timeframe, direction, score, source, regime, provisional, price, volume, breakdown
+             FROM signals WHERE time = (SELECT MAX(time) FROM signals) ORDER BY id",
+        ).map_err(db_err)?;
+        let rows = stmt.query_map([], |r| {
+            let regime: Option<String> = r.get(6)?;
+            let breakdown: String = r.get(10)?;
+            Ok(StoredSignal {
+                time: r.get::<_, i64>(0)? as u64,
+                symbol: r.get(1)?,
+                timeframe: r.get(2)?,
+                direction: r.get(3)?,
+                score: r.get(4)?,
+                source: r.get(5)?,
+                regime: regime.as_deref().and_then(from_text),
+                provisional: r.get(7)?,
+                price: r.get(8)?,
+                volume: r.get(9)?,
+                breakdown: serde_json::from_str(&breakdown).unwrap_or_default(),
+            })
+        }).map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    }
+
+    // Upserts orders updated at or after `since`
+    pub fn save_orders<'a>(&mut self, orders: impl Iterator<Item = &'a TrackedOrder>, since: u64) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(db_err)?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO orders (client_order_id, exchange_id, symbol, product_id, side, role, size, filled, avg_fill_price, state, reason, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            ).map_err(db_err)?;
+            for o in orders.filter(|o| o.updated_at >= since) {
+                stmt.execute(params![
+                    o.client_order_id, o.exchange_id.map(|id| id as i64), o.symbol, o.product_id as i64, o.side, o.role,
+                    o.size as i64, o.filled as i64, o.avg_fill_price, to_text(&o.state), o.reason, o.created_at as i64, o.updated_at as i64,
+                ]).map_err(db_err)?;
+            }
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    // Orders that had not reached a final state
+    pub fn open_orders(&self) -> Result<Vec<TrackedOrder>, String> {
+        let mut stmt = self.conn.prepare(&format!("{} WHERE state NOT IN ('filled', 'cancelled', 'rejected') ORDER BY created_at", ORDER_SELECT)).map_err(db_err)?;
+        let rows = stmt.query_map([], order_row).map_err(db_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(db_err)
+    }
+}
+
+const ORDER_SELECT: &str = "SELECT client_order_id, exchange_id, symbol, product_id, side, role, size, filled, avg_fill_price, state, reason, created_at, updated_at FROM orders";
+
+fn order_row(r: &rusqlite::Row) -> rusqlite::Result<TrackedOrder> {
+    let state: String = r.get(9)?;
+    // A state this build does not know is an error, not a guess
+    let state: OrderState = from_text(&state).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, format!("unknown order state '{}'", state).into())
+    })?;
+    Ok(TrackedOrder {
+        client_order_id: r.get(0)?,
+        exchange_id: r.get::<_, Option<i64>>(1)?.map(|id| id as u64),
+        symbol: r.get(2)?,
+        product_id: r.get::<_, i64>(3)? as u64,
+        side: r.get(4)?,
+        role: r.get(5)?,
+        size: r.get::<_, i64>(6)? as u64,
+        filled: r.get::<_, i64>(7)? as u64,
+        avg_fill_price: r.get(8)?,
+        state,
+        reason: r.get(10)?,
+        created_at: r.get::<_, i64>(11)? as u64,
+        updated_at: r.get::<_, i64>(12)? as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(timestamp: u64, close: f64) -> Candle {
+        Candle { open: close, high: close, low: close, close, volume: 1.0, timestamp }
+    }
+
+    fn order(id: &str, state: OrderState, updated_at: u64) -> TrackedOrder {
+        TrackedOrder {
+            client_order_id: id.to_string(),
+            exchange_id: Some(7),
+            symbol: "BTCUSD".to_string(),
+            product_id: 27,
+            side: "buy".to_string(),
+            role: "entry".to_string(),
+            size: 10,
+            filled: 4,
+            avg_fill_price: 100.5,
+            state,
+            reason: None,
+            created_at: updated_at,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn migrations_run_once() {
+        let mut db = Storage::open(":memory:").unwrap();
+        let version: usize = db.conn.pragma_query_value(None, "user_version", |r| r.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        db.migrate().unwrap();
+        assert!(db.load_candles("BTCUSD", "5m", u64::MAX, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn candles_round_trip_and_upsert() {
+        let mut db = Storage::open(":memory:").unwrap();
+        let batch = ("BTCUSD".to_string(), "5m".to_string(), (1..=4).map(|i| candle(i * 300, i as f64)).collect());
+        db.save_candles(&[batch]).unwrap();
+        db.save_candles(&[("BTCUSD".to_string(), "5m".to_string(), vec![candle(1200, 9.0)])]).unwrap();
+        // The last two before 1500, oldest first, with the replaced close
+        let closes: Vec<f64> = db.load_candles("BTCUSD", "5m", 1500, 2).unwrap().iter().map(|c| c.close).collect();
+        assert_eq!(closes, vec![3.0, 9.0]);
+        assert!(db.load_candles("BTCUSD", "1h", u64::MAX, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ticks_round_trip_and_prune() {
+        let mut db = Storage::open(":memory:").unwrap();
+        let ticks: Vec<(String, f64, f64, u64)> = (0..5).map(|i| ("BTCUSD".to_string(), 100.0 + i as f64, 1.0, i * 10))
+            .chain([("ETHUSD".to_string(), 5.0, 2.0, 30)])
+            .collect();
+        db.save_ticks(&ticks).unwrap();
+        let loaded = db.load_ticks(2).unwrap();
+        assert_eq!(loaded["BTCUSD"], vec![(103.0, 1.0, 30), (104.0, 1.0, 40)]);
+        assert_eq!(loaded["ETHUSD"], vec![(5.0, 2.0, 30)]);
+        assert_eq!(db.prune_ticks(30).unwrap(), 3);
+        assert_eq!(db.load_ticks(10).unwrap()["BTCUSD"].len(), 2);
+    }
+
+    #[test]
+    fn last_signals_returns_the_latest_cycle() {
+        let db = Storage::open(":memory:").unwrap();
+        let signal = |time: u64, symbol: &str| StoredSignal {
+            time,
+            symbol: symbol.to_string(),
+            timeframe: "5m".to_string(),
+            direction: "buy".to_string(),
+            score: 72.5,
+            source: "scoring".to_string(),
+            regime: Some(Regime::TrendingUp),
+            provisional: false,
+            price: 100.0,
+            volume: 3.0,
+            breakdown: vec![ComponentScore { name: "trend_5m".to_string(), weight: 30.0, value: 1.0, points: 30.0 }],
+        };
+        db.save_signal(&signal(100, "BTCUSD")).unwrap();
+        db.save_signal(&signal(400, "BTCUSD")).unwrap();
+        db.save_signal(&signal(400, "ETHUSD")).unwrap();
+        let last = db.last_signals().unwrap();
+        assert_eq!(last.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), vec!["BTCUSD", "ETHUSD"]);
+        assert_eq!(last[0].regime, Some(Regime::TrendingUp));
+        assert_eq!(last[0].breakdown[0].name, "trend_5m");
+    }
+
+    #[test]
+    fn open_orders_skip_final_states_and_reject_unknown_ones() {
+        let mut db = Storage::open(":memory:").unwrap();
+        let orders = [
+            order("a", OrderState::PartiallyFilled, 100),
+            order("b", OrderState::Filled, 100),
+            order("c", OrderState::Acknowledged, 50),
+        ];
+        // Only orders updated since 80 are written
+        db.save_orders(orders.iter(), 80).unwrap();
+        let open = db.open_orders().unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!((open[0].client_order_id.as_str(), open[0].state, open[0].filled), ("a", OrderState::PartiallyFilled, 4));
+        assert_eq!(open[0].exchange_id, Some(7));
+        db.conn.execute("UPDATE orders SET state = 'expired' WHERE client_order_id = 'a'", []).unwrap();
+        assert!(db.open_orders().is_err());
+    }
+}
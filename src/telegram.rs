@@ -11,10 +11,20 @@ impl TelegramBot {
     }
     pub async fn send_signal(&self, market: &str, timeframe: &str, signal: &str) -> Result<(), reqwest::Error> {
         let message = format!("{} {}: {} signal", market, timeframe, signal);
+        self.send_message(&message).await
+    }
+    pub async fn send_alert(&self, market: &str, timeframe: &str, alert: &str) -> Result<(), reqwest::Error> {
+        let message = format!("{} {}: {}", market, timeframe, alert);
+        self.send_message(&message).await
+    }
+    pub async fn send_notice(&self, notice: &str) -> Result<(), reqwest::Error> {
+        self.send_message(notice).await
+    }
+    async fn send_message(&self, message: &str) -> Result<(), reqwest::Error> {
         let url = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
         let params = [
             ("chat_id", self.chat_id.as_str()),
-            ("text", &message),
+            ("text", message),
         ];
         let client = reqwest::Client::new();
         client.post(&url)
@@ -0,0 +1,135 @@
+// Volume spike detection (rolling z-score or median absolute deviation) and
+// relative volume against the same time of day on previous days
+use std::collections::HashMap;
+
+use crate::delta::Candle;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct VolumeConfig {
+    // Candles before the last one that form the baseline
+    pub window: usize,
+    // "zscore" or "mad"
+    pub method: String,
+    // Score at or above which the last candle is a spike
+    pub spike_threshold: f64,
+    // Previous days compared for relative volume
+    pub rvol_days: usize,
+    // RVOL at or above which activity is unusual
+    pub rvol_threshold: f64,
+    // Send "unusual activity" alerts for spikes on these timeframes
+    pub alert_timeframes: Vec<String>,
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        Self {
+            window: 50,
+            method: "mad".to_string(),
+            spike_threshold: 3.0,
+            rvol_days: 5,
+            rvol_threshold: 2.0,
+            alert_timeframes: vec!["15m".to_string(), "1h".to_string()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct VolumeReading {
+    pub volume: f64,
+    pub score: f64,
+    pub rvol: Option<f64>,
+    pub spike: bool,
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let n = values.len();
+    if n.is_multiple_of(2) { (values[n / 2 - 1] + values[n / 2]) / 2.0 } else { values[n / 2] }
+}
+
+// Standard score of `value` against `baseline`
+pub fn zscore(value: f64, baseline: &[f64]) -> Option<f64> {
+    if baseline.len() < 2 {
+        return None;
+    }
+    let mean = baseline.iter().sum::<f64>() / baseline.len() as f64;
+    let var = baseline.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (baseline.len() - 1) as f64;
+    let sd = var.sqrt();
+    if sd > 0.0 { Some((value - mean) / sd) } else { None }
+}
+
+// Robust score using the median and scaled median absolute deviation
+pub fn mad_score(value: f64, baseline: &[f64]) -> Option<f64> {
+    if baseline.len() < 2 {
+        return None;
+    }
+    let mut vals = baseline.to_vec();
+    let med = median(&mut vals);
+    let mut dev: Vec<f64> = baseline.iter().map(|v| (v - med).abs()).collect();
+    let mad = median(&mut dev) * 1.4826;
+    if mad > 0.0 { Some((value - med) / mad) } else { None }
+}
+
+// Last candle's volume over the average of candles at the same time of day
+pub fn relative_volume(candles: &[Candle], days: usize) -> Option<f64> {
+    let last = candles.last()?;
+    let tod = last.timestamp % 86400;
+    let same_time: Vec<f64> = candles[..candles.len() - 1].iter().rev()
+        .filter(|c| c.timestamp % 86400 == tod && c.timestamp < last.timestamp)
+        .take(days)
+        .map(|c| c.volume)
+        .collect();
+    if same_time.is_empty() {
+        return None;
+    }
+    let avg = same_time.iter().sum::<f64>() / same_time.len() as f64;
+    if avg > 0.0 { Some(last.volume / avg) } else { None }
+}
+
+// Scores the last candle's volume against the preceding window
+pub fn analyze(candles: &[Candle], cfg: &VolumeConfig) -> Option<VolumeReading> {
+    let last = candles.last()?;
+    let start = candles.len().saturating_sub(cfg.window + 1);
+    let baseline: Vec<f64> = candles[start..candles.len() - 1].iter().map(|c| c.volume).collect();
+    let score = match cfg.method.as_str() {
+        "zscore" => zscore(last.volume, &baseline)?,
+        _ => mad_score(last.volume, &baseline)?,
+    };
+    let rvol = relative_volume(candles, cfg.rvol_days);
+    let spike = score >= cfg.spike_threshold || rvol.is_some_and(|r| r >= cfg.rvol_threshold);
+    Some(VolumeReading { volume: last.volume, score, rvol, spike })
+}
+
+// Reports each spike candle once per symbol and timeframe
+#[derive(Default)]
+pub struct VolumeAlerter {
+    last_alerted: HashMap<(String, String), u64>,
+}
+
+impl VolumeAlerter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, symbol: &str, candles: &HashMap<String, Vec<Candle>>, cfg: &VolumeConfig) -> Vec<(String, VolumeReading)> {
+        let mut alerts = Vec::new();
+        for tf in &cfg.alert_timeframes {
+            let series = match candles.get(tf) {
+                Some(s) => s,
+                None => continue,
+            };
+            let (reading, ts) = match (analyze(series, cfg), series.last()) {
+                (Some(r), Some(c)) if r.spike => (r, c.timestamp),
+                _ => continue,
+            };
+            let key = (symbol.to_string(), tf.clone());
+            if self.last_alerted.get(&key) == Some(&ts) {
+                continue;
+            }
+            self.last_alerted.insert(key, ts);
+            alerts.push((tf.clone(), reading));
+        }
+        alerts
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 // Web dashboard using Warp
 use warp::Filter;
@@ -10,14 +11,40 @@ pub struct SignalInfo {
     pub coin: String,
     pub timeframe: String,
     pub signal: String,
-    pub strength: i32,
+    pub strength: i32, // normalized score, 0-100
+    pub breakdown: Vec<crate::scoring::ComponentScore>,
+    pub regime: Option<crate::regime::Regime>,
+    // Raised on a forming candle (intrabar preview)
+    pub provisional: bool,
     pub volume: f64,
     pub timestamp: String,
 }
 
 pub type SignalStore = Arc<Mutex<Vec<SignalInfo>>>;
 
-pub async fn run_web_dashboard_with_signals(signal_store: SignalStore) {
+// Latest support/resistance zones and pivots per symbol
+pub type LevelStore = Arc<Mutex<HashMap<String, crate::levels::SymbolLevels>>>;
+
+// Latest regime per symbol and timeframe
+pub type RegimeStore = Arc<Mutex<HashMap<String, std::collections::BTreeMap<String, crate::regime::RegimeReading>>>>;
+
+// Latest relative strength ranking, strongest first
+pub type RankingStore = Arc<Mutex<Vec<crate::ranking::RankEntry>>>;
+
+// Latest volume profile per symbol
+pub type ProfileStore = Arc<Mutex<HashMap<String, crate::profile::VolumeProfile>>>;
+
+// Hit rates of past signals by symbol, timeframe, strength and component
+pub type OutcomeStore = Arc<Mutex<crate::outcomes::OutcomeSummary>>;
+
+// Paper trading account, if enabled
+pub type PaperStore = Arc<Mutex<Option<crate::paper::PaperSnapshot>>>;
+
+// Kill switch, exposure and recent rejections of live execution, if enabled
+pub type RiskStore = Arc<Mutex<Option<crate::risk::RiskSnapshot>>>;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn run_web_dashboard_with_signals(signal_store: SignalStore, level_store: LevelStore, regime_store: RegimeStore, ranking_store: RankingStore, profile_store: ProfileStore, outcome_store: OutcomeStore, paper_store: PaperStore, risk_store: RiskStore) {
     let dashboard = warp::path::end().map(|| {
         warp::reply::html(r#"
         <!DOCTYPE html>
@@ -41,6 +68,9 @@ pub async fn run_web_dashboard_with_signals(signal_store: SignalStore) {
                 .strength-low { width: 20px; background: #ff4d4d; }
                 .strength-med { width: 40px; background: #ffd700; }
                 .strength-high { width: 60px; background: #00ff99; }
+                .regime { color: #aaa; font-size: 0.8rem; }
+                .provisional { opacity: 0.6; font-style: italic; }
+                .kill { background: #ff4d4d; color: #181818; font-weight: bold; padding: 0.4rem; border-radius: 4px; }
                 td[title] { cursor: help; }
             </style>
             <script>
@@ -53,25 +83,120 @@ pub async fn run_web_dashboard_with_signals(signal_store: SignalStore) {
                         let barClass = 'strength-low';
                         if (s.strength >= 80) barClass = 'strength-high';
                         else if (s.strength >= 60) barClass = 'strength-med';
-                        tbody.innerHTML += `<tr>
+                        const breakdown = (s.breakdown || [])
+                            .filter(c => Math.abs(c.points) >= 0.5)
+                            .map(c => `${c.name}: ${c.points >= 0 ? '+' : ''}${c.points.toFixed(1)}`)
+                            .join('\n');
+                        tbody.innerHTML += `<tr class='${s.provisional ? 'provisional' : ''}' title='${s.provisional ? 'Provisional: raised on a forming candle' : ''}'>
                             <td title='${s.coin} perpetual'>${s.coin}</td>
                             <td title='${s.timeframe}'>${s.timeframe}</td>
-                            <td class='${s.signal}' title='${s.signal} signal'>${s.signal.charAt(0).toUpperCase() + s.signal.slice(1)}</td>
-                            <td title='${s.strength}/140 points'><span class='strength-bar ${barClass}'></span>${s.strength}</td>
+                            <td class='${s.signal}' title='${s.signal} signal'>${s.signal.charAt(0).toUpperCase() + s.signal.slice(1)}${s.provisional ? ' (prov.)' : ''}</td>
+                            <td title='${s.strength}/100\n${breakdown}'><span class='strength-bar ${barClass}'></span>${s.strength}</td>
+                            <td class='regime' title='Primary timeframe regime'>${(s.regime || '-').replace('_', ' ')}</td>
                             <td title='Volume'>${Math.round(s.volume/1000)}k</td>
                             <td title='Signal time'>${s.timestamp}</td>
                         </tr>`;
                     }
                 }
+                async function fetchProfiles() {
+                    const res = await fetch('/api/profiles');
+                    const data = await res.json();
+                    const tbody = document.getElementById('profiles-body');
+                    tbody.innerHTML = '';
+                    for (const [coin, p] of Object.entries(data).sort()) {
+                        const pos = p.price > p.vah ? 'above' : (p.price < p.val ? 'below' : 'inside');
+                        const cls = pos === 'above' ? 'buy' : (pos === 'below' ? 'sell' : '');
+                        tbody.innerHTML += `<tr>
+                            <td>${coin}</td>
+                            <td>${p.price.toPrecision(6)}</td>
+                            <td title='Point of control'>${p.poc.toPrecision(6)}</td>
+                            <td title='Value area high'>${p.vah.toPrecision(6)}</td>
+                            <td title='Value area low'>${p.val.toPrecision(6)}</td>
+                            <td class='${cls}'>${pos}</td>
+                        </tr>`;
+                    }
+                }
+                async function fetchOutcomes() {
+                    const res = await fetch('/api/outcomes');
+                    const data = await res.json();
+                    const tbody = document.getElementById('outcomes-body');
+                    tbody.innerHTML = '';
+                    const pct = v => v === undefined ? '-' : v.toFixed(0) + '%';
+                    for (const [bucket, h] of Object.entries(data.by_strength || {})) {
+                        tbody.innerHTML += `<tr>
+                            <td>${bucket}</td>
+                            <td>${h.signals}</td>
+                            <td title='Avg ${(h.avg_return['1h'] || 0).toFixed(2)}%'>${pct(h.hit_rate['1h'])}</td>
+                            <td title='Avg ${(h.avg_return['4h'] || 0).toFixed(2)}%'>${pct(h.hit_rate['4h'])}</td>
+                            <td title='Avg ${(h.avg_return['24h'] || 0).toFixed(2)}%'>${pct(h.hit_rate['24h'])}</td>
+                            <td title='Target hit before stop'>${pct(h.target_first_rate)}</td>
+                            <td>${h.avg_mfe_pct.toFixed(2)}% / ${h.avg_mae_pct.toFixed(2)}%</td>
+                        </tr>`;
+                    }
+                }
+                async function fetchPaper() {
+                    const res = await fetch('/api/paper');
+                    const a = await res.json();
+                    const tbody = document.getElementById('paper-body');
+                    tbody.innerHTML = '';
+                    if (!a) {
+                        document.getElementById('paper-summary').textContent = 'Paper trading is disabled.';
+                        return;
+                    }
+                    document.getElementById('paper-summary').textContent =
+                        `Equity ${a.equity.toFixed(2)} | Balance ${a.balance.toFixed(2)} | Margin ${a.used_margin.toFixed(2)} used, ${a.free_margin.toFixed(2)} free | ` +
+                        `Unrealized ${a.unrealized_pnl.toFixed(2)} | Realized ${a.realized_pnl.toFixed(2)} | Fees ${a.fees_paid.toFixed(2)} | Funding ${a.funding_paid.toFixed(2)}`;
+                    for (const p of a.positions) {
+                        tbody.innerHTML += `<tr>
+                            <td>${p.symbol}</td>
+                            <td class='${p.side === 'long' ? 'buy' : 'sell'}'>${p.side}</td>
+                            <td>${p.quantity.toPrecision(4)}</td>
+                            <td>${p.entry_price.toPrecision(6)}</td>
+                            <td>${p.mark_price.toPrecision(6)}</td>
+                            <td class='${p.unrealized_pnl >= 0 ? 'buy' : 'sell'}'>${p.unrealized_pnl.toFixed(2)}</td>
+                            <td>${p.margin.toFixed(2)}</td>
+                            <td>${p.funding.toFixed(2)}</td>
+                        </tr>`;
+                    }
+                }
+                async function fetchRisk() {
+                    const res = await fetch('/api/risk');
+                    const r = await res.json();
+                    const status = document.getElementById('risk-status');
+                    const tbody = document.getElementById('rejections-body');
+                    tbody.innerHTML = '';
+                    if (!r) {
+                        status.className = 'regime';
+                        status.textContent = 'Live execution is disabled.';
+                        return;
+                    }
+                    status.className = r.kill_switch ? 'kill' : 'regime';
+                    status.textContent = (r.kill_switch ? `KILL SWITCH ACTIVE: ${r.kill_switch.reason} | ` : 'Kill switch armed | ') +
+                        `Day PnL ${r.day_pnl_pct.toFixed(2)}% | ${r.open_positions} positions | Gross ${r.gross_exposure.toFixed(2)} | ` +
+                        `Net ${r.net_exposure.toFixed(2)} | Leverage ${r.leverage.toFixed(2)}x`;
+                    for (const j of r.rejections) {
+                        tbody.innerHTML += `<tr>
+                            <td>${new Date(j.time * 1000).toLocaleTimeString()}</td>
+                            <td>${j.symbol}</td>
+                            <td class='${j.side}'>${j.side}</td>
+                            <td>${j.notional.toFixed(2)}</td>
+                            <td>${j.reason}</td>
+                        </tr>`;
+                    }
+                }
                 setInterval(fetchSignals, 5000);
-                window.onload = fetchSignals;
+                setInterval(fetchPaper, 10000);
+                setInterval(fetchRisk, 10000);
+                setInterval(fetchProfiles, 30000);
+                setInterval(fetchOutcomes, 60000);
+                window.onload = () => { fetchSignals(); fetchProfiles(); fetchOutcomes(); fetchPaper(); fetchRisk(); };
             </script>
         </head>
         <body>
             <header>AI Agent Dashboard</header>
             <div class='container'>
                 <h2>Summary</h2>
-                <p>Monitoring <b>146</b> perpetual coins across 5 timeframes (5m, 15m, 1h, 4h, 1d) using EMA 12/26. Signals are sent to Telegram in real-time.</p>
+                <p>Monitoring <b>146</b> perpetual coins across 5 timeframes (5m, 15m, 1h, 4h, 1d) using EMA 12/26 with weighted MACD, volume and RSI confirmation. Signals are sent to Telegram in real-time.</p>
                 <h2>Latest Signals</h2>
                 <table>
                     <thead>
@@ -80,6 +205,7 @@ pub async fn run_web_dashboard_with_signals(signal_store: SignalStore) {
                             <th>TF</th>
                             <th>Signal</th>
                             <th>Strength</th>
+                            <th>Regime</th>
                             <th>Vol</th>
                             <th>Time</th>
                         </tr>
@@ -88,6 +214,66 @@ pub async fn run_web_dashboard_with_signals(signal_store: SignalStore) {
                         <!-- Live signals will be injected here -->
                     </tbody>
                 </table>
+                <h2>Risk</h2>
+                <p id='risk-status' class='regime'></p>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>Time</th>
+                            <th>Coin</th>
+                            <th>Side</th>
+                            <th>Notional</th>
+                            <th>Rejected because</th>
+                        </tr>
+                    </thead>
+                    <tbody id='rejections-body'></tbody>
+                </table>
+                <h2>Paper Positions</h2>
+                <p id='paper-summary' class='regime'></p>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>Coin</th>
+                            <th>Side</th>
+                            <th>Qty</th>
+                            <th>Entry</th>
+                            <th>Mark</th>
+                            <th>uPnL</th>
+                            <th>Margin</th>
+                            <th>Funding</th>
+                        </tr>
+                    </thead>
+                    <tbody id='paper-body'></tbody>
+                </table>
+                <h2>Volume Profile</h2>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>Coin</th>
+                            <th>Price</th>
+                            <th>POC</th>
+                            <th>VAH</th>
+                            <th>VAL</th>
+                            <th>Position</th>
+                        </tr>
+                    </thead>
+                    <tbody id='profiles-body'></tbody>
+                </table>
+                <h2>Signal Outcomes by Strength</h2>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>Strength</th>
+                            <th>Signals</th>
+                            <th>Hit 1h</th>
+                            <th>Hit 4h</th>
+                            <th>Hit 24h</th>
+                            <th>Target first</th>
+                            <th>MFE / MAE</th>
+                        </tr>
+                    </thead>
+                    <tbody id='outcomes-body'></tbody>
+                </table>
             </div>
         </body>
         </html>
@@ -105,7 +291,90 @@ pub async fn run_web_dashboard_with_signals(signal_store: SignalStore) {
             }
         });
 
-    let routes = dashboard.or(api);
+    let levels_store = level_store.clone();
+    let levels_all = warp::path!("api" / "levels")
+        .and(warp::get())
+        .and_then(move || {
+            let level_store = levels_store.clone();
+            async move {
+                let levels = level_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*levels))
+            }
+        });
+
+    let levels_symbol = warp::path!("api" / "levels" / String)
+        .and(warp::get())
+        .and_then(move |symbol: String| {
+            let level_store = level_store.clone();
+            async move {
+                let levels = level_store.lock().await;
+                match levels.get(&symbol) {
+                    Some(l) => Ok(warp::reply::json(l)),
+                    None => Err(warp::reject::not_found()),
+                }
+            }
+        });
+
+    let regimes = warp::path!("api" / "regimes")
+        .and(warp::get())
+        .and_then(move || {
+            let regime_store = regime_store.clone();
+            async move {
+                let regimes = regime_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*regimes))
+            }
+        });
+
+    let rankings = warp::path!("api" / "rankings")
+        .and(warp::get())
+        .and_then(move || {
+            let ranking_store = ranking_store.clone();
+            async move {
+                let ranking = ranking_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*ranking))
+            }
+        });
+
+    let profiles = warp::path!("api" / "profiles")
+        .and(warp::get())
+        .and_then(move || {
+            let profile_store = profile_store.clone();
+            async move {
+                let profiles = profile_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*profiles))
+            }
+        });
+
+    let outcomes = warp::path!("api" / "outcomes")
+        .and(warp::get())
+        .and_then(move || {
+            let outcome_store = outcome_store.clone();
+            async move {
+                let summary = outcome_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*summary))
+            }
+        });
+
+    let paper = warp::path!("api" / "paper")
+        .and(warp::get())
+        .and_then(move || {
+            let paper_store = paper_store.clone();
+            async move {
+                let account = paper_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*account))
+            }
+        });
+
+    let risk = warp::path!("api" / "risk")
+        .and(warp::get())
+        .and_then(move || {
+            let risk_store = risk_store.clone();
+            async move {
+                let snapshot = risk_store.lock().await;
+                Ok::<_, warp::Rejection>(warp::reply::json(&*snapshot))
+            }
+        });
+
+    let routes = dashboard.or(api).or(levels_all).or(levels_symbol).or(regimes).or(rankings).or(profiles).or(outcomes).or(paper).or(risk);
     warp::serve(routes).run(([0, 0, 0, 0], 8080)).await;
 }
-}